@@ -0,0 +1,395 @@
+//! The offline media archive.
+//!
+//! If archiving is enabled for a back-end, the media content of the items of each requested
+//! channel is downloaded into the archive directory. Downloads of archived items are served from
+//! the archive, and archived items that are no longer provided by the back-end are kept in the
+//! feed. A retention policy per channel determines which items are kept in the archive.
+//!
+//! The archive directory contains a subdirectory per back-end, which contains the downloaded
+//! files (in `files`) and an index with the metadata of the archived items per channel (in
+//! `channels`).
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, Utc};
+use rocket::serde::json::{from_slice, serde_json};
+use rocket::serde::{Deserialize, Serialize};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use url::form_urlencoded;
+
+use crate::backends::{Backend, Channel, Item};
+use crate::{Config, Result};
+
+/// The archive configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct ArchiveConfig {
+    /// The path of the archive directory.
+    ///
+    /// If not set, archiving is disabled for all back-ends.
+    path: Option<PathBuf>,
+
+    /// The maximum number of items to keep per channel (if any).
+    max_items: Option<usize>,
+
+    /// The maximum age of items to keep (in seconds, if any).
+    max_age: Option<u64>,
+
+    /// The maximum total size of the items to keep per channel (in bytes, if any).
+    max_bytes: Option<u64>,
+}
+
+/// The offline media archive.
+///
+/// The archive can be cheaply cloned; all clones share the same state.
+#[derive(Clone, Debug)]
+pub(crate) struct Archive {
+    /// The archive configuration.
+    config: ArchiveConfig,
+
+    /// The IDs of the back-ends for which archiving is enabled.
+    enabled_backends: HashSet<String>,
+
+    /// The channels (back-end and channel ID) that are currently being archived.
+    archiving: Arc<Mutex<HashSet<(String, String)>>>,
+}
+
+impl Archive {
+    /// Creates the archive using the provided configuration.
+    pub(crate) fn new(config: &Config) -> Self {
+        let enabled_backends = config
+            .backends
+            .iter()
+            .filter(|(_, settings)| settings.archive)
+            .map(|(backend_id, _)| backend_id.clone())
+            .collect();
+
+        Self {
+            config: config.archive.clone(),
+            enabled_backends,
+            archiving: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Returns the archive directory of the back-end, if archiving is enabled for it.
+    fn backend_path(&self, backend_id: &str) -> Option<PathBuf> {
+        let path = self.config.path.as_ref()?;
+
+        self.enabled_backends
+            .contains(backend_id)
+            .then(|| path.join(backend_id))
+    }
+
+    /// Returns the path of the index file of a channel, if archiving is enabled for the back-end.
+    fn index_path(&self, backend_id: &str, channel_id: &str) -> Option<PathBuf> {
+        let file_name = form_urlencoded::byte_serialize(channel_id.as_bytes()).collect::<String>();
+
+        self.backend_path(backend_id)
+            .map(|path| path.join("channels").join(format!("{file_name}.json")))
+    }
+
+    /// Returns the path of a download file in the archive, if archiving is enabled for the
+    /// back-end.
+    fn file_path(&self, backend_id: &str, file: &Path) -> Option<PathBuf> {
+        self.backend_path(backend_id)
+            .map(|path| path.join("files").join(file))
+    }
+
+    /// Returns the path of a download file in the archive if it has been archived.
+    ///
+    /// If the download file is not archived as-is, an archived file with the same name but a
+    /// different extension is looked for, since the file may have been requested in another
+    /// (transcoded) format.
+    pub(crate) async fn local_file(&self, backend_id: &str, file: &Path) -> Option<PathBuf> {
+        let file_path = self.file_path(backend_id, file)?;
+        if is_file(&file_path).await {
+            return Some(file_path);
+        }
+
+        let file_stem = file_path.file_stem()?.to_owned();
+        let mut entries = fs::read_dir(file_path.parent()?).await.ok()?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext != "part")
+                && path.file_stem() == Some(&file_stem)
+                && is_file(&path).await
+            {
+                return Some(path);
+            }
+        }
+
+        None
+    }
+
+    /// Merges the archived items of a channel into the channel.
+    ///
+    /// The enclosures of items that have been archived get their actual lengths, and archived
+    /// items that are no longer in the channel are added to it. If an item limit is provided, only
+    /// the newest items up to the limit are kept afterwards.
+    pub(crate) async fn merge(
+        &self,
+        backend_id: &str,
+        channel_id: &str,
+        channel: &mut Channel,
+        item_limit: Option<usize>,
+    ) -> Result<()> {
+        let Some(index_path) = self.index_path(backend_id, channel_id) else {
+            return Ok(());
+        };
+
+        let archived_items = read_index(&index_path).await?;
+        for archived_item in archived_items {
+            match channel
+                .items
+                .iter_mut()
+                .find(|item| item.guid == archived_item.guid)
+            {
+                Some(item) => item.enclosure.length = archived_item.enclosure.length,
+                None => channel.items.push(archived_item),
+            }
+        }
+        channel.items.sort_by_key(|item| Reverse(item.published_at));
+        if let Some(item_limit) = item_limit {
+            channel.items.truncate(item_limit);
+        }
+
+        Ok(())
+    }
+
+    /// Archives the items of a channel in the background, if archiving is enabled for the
+    /// back-end.
+    ///
+    /// The items that should be retained according to the retention policy and that are not
+    /// archived yet are downloaded; archived items that should no longer be retained are removed.
+    /// The media content is downloaded using the provided HTTP client of the back-end.
+    /// If the channel is already being archived, nothing happens.
+    pub(crate) fn update(
+        &self,
+        backend: Box<dyn Backend>,
+        client: reqwest::Client,
+        backend_id: &str,
+        channel_id: &str,
+        items: &[Item],
+    ) {
+        if self.index_path(backend_id, channel_id).is_none() {
+            return;
+        }
+
+        let key = (backend_id.to_owned(), channel_id.to_owned());
+        let mut archiving = self.archiving.lock().expect("lock is not poisoned");
+        if !archiving.insert(key.clone()) {
+            return;
+        }
+        drop(archiving);
+
+        let archive = self.clone();
+        let items = items.to_vec();
+        tokio::spawn(async move {
+            let (backend_id, channel_id) = &key;
+            if let Err(err) = archive
+                .archive_items(&*backend, &client, backend_id, channel_id, items)
+                .await
+            {
+                eprintln!("💥 Could not archive channel {channel_id} on {backend_id}: {err}");
+            }
+
+            archive
+                .archiving
+                .lock()
+                .expect("lock is not poisoned")
+                .remove(&key);
+        });
+    }
+
+    /// Archives the items of a channel according to the retention policy.
+    async fn archive_items(
+        &self,
+        backend: &dyn Backend,
+        client: &reqwest::Client,
+        backend_id: &str,
+        channel_id: &str,
+        items: Vec<Item>,
+    ) -> Result<()> {
+        let index_path = self
+            .index_path(backend_id, channel_id)
+            .expect("archiving is enabled");
+        let mut archived_items = read_index(&index_path).await?;
+        let archived_guids = archived_items
+            .iter()
+            .map(|item| item.guid.clone())
+            .collect::<HashSet<_>>();
+
+        // Determine the new items that should be retained and download them.
+        let mut all_items = archived_items
+            .iter()
+            .chain(
+                items
+                    .iter()
+                    .filter(|item| !archived_guids.contains(&item.guid)),
+            )
+            .collect::<Vec<_>>();
+        all_items.sort_by_key(|item| Reverse(item.published_at));
+        let retained_guids = all_items
+            .iter()
+            .take(self.retained_count(all_items.iter().copied()))
+            .map(|item| item.guid.clone())
+            .collect::<HashSet<_>>();
+        let new_items = items.into_iter().filter(|item| {
+            !archived_guids.contains(&item.guid) && retained_guids.contains(&item.guid)
+        });
+        for mut item in new_items {
+            let file_path = self
+                .file_path(backend_id, &item.enclosure.file)
+                .expect("archiving is enabled");
+            println!(
+                "💾 Archiving {} of {channel_id} on {backend_id}...",
+                item.guid
+            );
+            // Items can fail to download, e.g. when they have been removed or are not available
+            // yet, which should not prevent the other items from being archived.
+            match download(backend, client, &item, &file_path).await {
+                Ok(length) => item.enclosure.length = length,
+                Err(err) => {
+                    eprintln!(
+                        "💥 Could not archive {} of {channel_id} on {backend_id}: {err}",
+                        item.guid
+                    );
+                    continue;
+                }
+            }
+            archived_items.push(item);
+            archived_items.sort_by_key(|item| Reverse(item.published_at));
+            write_index(&index_path, &archived_items).await?;
+        }
+
+        // Remove the archived items that should no longer be retained.
+        let retained = self.retained_count(archived_items.iter());
+        for item in archived_items.drain(retained..) {
+            let file_path = self
+                .file_path(backend_id, &item.enclosure.file)
+                .expect("archiving is enabled");
+            println!(
+                "🗑️ Removing archived {} of {channel_id} on {backend_id}...",
+                item.guid
+            );
+            if let Err(err) = fs::remove_file(&file_path).await {
+                eprintln!("💥 Could not remove {}: {err}", file_path.display());
+            }
+        }
+        write_index(&index_path, &archived_items).await?;
+
+        Ok(())
+    }
+
+    /// Returns the number of items that should be retained according to the retention policy.
+    ///
+    /// The items need to be sorted from newest to oldest.
+    fn retained_count<'a>(&self, items: impl Iterator<Item = &'a Item>) -> usize {
+        let oldest = self
+            .config
+            .max_age
+            .map(|max_age| Utc::now() - Duration::seconds(max_age as i64));
+
+        let mut total_bytes = 0;
+        items
+            .into_iter()
+            .take(self.config.max_items.unwrap_or(usize::MAX))
+            .take_while(|item| oldest.is_none_or(|oldest| item.published_at >= oldest))
+            .take_while(|item| {
+                total_bytes += item.enclosure.length;
+                self.config
+                    .max_bytes
+                    .is_none_or(|max_bytes| total_bytes <= max_bytes)
+            })
+            .count()
+    }
+}
+
+/// Returns whether the provided path exists and is a file.
+async fn is_file(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_file())
+}
+
+/// Reads the archived items from the index file at the provided path.
+///
+/// If the index file does not exist yet, no items are returned.
+async fn read_index(path: &Path) -> Result<Vec<Item>> {
+    match fs::read(path).await {
+        Ok(data) => Ok(from_slice(&data)?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Writes the archived items to the index file at the provided path.
+async fn write_index(path: &Path, items: &[Item]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, serde_json::to_vec(items)?).await?;
+
+    Ok(())
+}
+
+/// Downloads the media content of an item to the provided path and returns its length.
+///
+/// The media content is first downloaded to a temporary file that is moved into place once the
+/// download has finished. If the download fails, the temporary file is removed.
+async fn download(
+    backend: &dyn Backend,
+    client: &reqwest::Client,
+    item: &Item,
+    path: &Path,
+) -> Result<u64> {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    let part_path = PathBuf::from(part_path);
+
+    match download_to(backend, client, item, &part_path).await {
+        Ok(length) => {
+            fs::rename(&part_path, path).await?;
+
+            Ok(length)
+        }
+        Err(err) => {
+            if let Err(remove_err) = fs::remove_file(&part_path).await {
+                if remove_err.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("💥 Could not remove {}: {remove_err}", part_path.display());
+                }
+            }
+
+            Err(err)
+        }
+    }
+}
+
+/// Downloads the media content of an item to the provided (temporary) path and returns its
+/// length.
+async fn download_to(
+    backend: &dyn Backend,
+    client: &reqwest::Client,
+    item: &Item,
+    path: &Path,
+) -> Result<u64> {
+    let url = backend.redirect_url(&item.enclosure.file).await?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut file = File::create(path).await?;
+    let mut length = 0;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        length += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    Ok(length)
+}
@@ -4,44 +4,605 @@
 //! It must provide a methods to retrieve a channel and its items and a method to return the
 //! redirect URL for some path that points to media within context of the back-end.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use enum_dispatch::enum_dispatch;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
 use reqwest::Url;
+use rocket::serde::{Deserialize, Serialize};
+use url::Host;
+use youtube_dl::YoutubeDl;
 
-use crate::{Error, Result};
+use crate::cache::{Cache, CacheTtls};
+use crate::download::DownloadMode;
+use crate::transcode::MediaFormat;
+use crate::{Config, Error, Result};
 
+pub(crate) mod bandcamp;
+pub(crate) mod internet_archive;
+pub(crate) mod invidious;
+pub(crate) mod local;
 pub(crate) mod mixcloud;
+pub(crate) mod peertube;
+pub(crate) mod rss;
+pub(crate) mod soundcloud;
+pub(crate) mod twitch;
+pub(crate) mod vimeo;
 pub(crate) mod youtube;
+pub(crate) mod ytdl;
 
-/// Retrieves the back-end for the provided ID (if supported).
-pub(crate) fn get(backend: &str) -> Result<Backends> {
-    match backend {
-        "mixcloud" => Ok(Backends::Mixcloud(mixcloud::backend())),
-        "youtube" => Ok(Backends::YouTube(youtube::backend())),
-        _ => Err(Error::UnsupportedBackend(backend.to_string())),
+/// The factory that creates a back-end within some context.
+type BackendFactory = Arc<dyn Fn(&BackendContext<'_>) -> Box<dyn Backend> + Send + Sync>;
+
+/// The registry of available back-ends.
+///
+/// Each back-end is registered with its information (see [`BackendInfo`]) and a factory that
+/// creates it. The built-in back-ends are registered by [`Registry::with_builtins`]; other
+/// back-ends can be registered when embedding Podbringer (see [`crate::setup_with`]).
+#[derive(Clone, Default)]
+pub struct Registry {
+    /// The registered back-ends (in order of registration).
+    entries: Vec<(BackendInfo, BackendFactory)>,
+}
+
+impl Registry {
+    /// Creates a new registry without any back-ends.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new registry with the built-in back-ends.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(bandcamp::INFO, |ctx| {
+                Box::new(bandcamp::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(internet_archive::INFO, |ctx| {
+                Box::new(internet_archive::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(invidious::INFO, |ctx| {
+                Box::new(invidious::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(local::INFO, |ctx| Box::new(local::backend(ctx.config)))
+            .register(mixcloud::INFO, |ctx| {
+                Box::new(mixcloud::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(peertube::INFO, |ctx| {
+                Box::new(peertube::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(rss::INFO, |ctx| {
+                Box::new(rss::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(soundcloud::INFO, |ctx| {
+                Box::new(soundcloud::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(twitch::INFO, |ctx| {
+                Box::new(twitch::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(vimeo::INFO, |ctx| {
+                Box::new(vimeo::backend(ctx.cache, &ctx.settings()))
+            })
+            .register(youtube::INFO, |ctx| {
+                let invidious_settings = ctx.config.backend(invidious::INFO.id);
+
+                Box::new(youtube::backend(
+                    ctx.cache,
+                    &ctx.settings(),
+                    &invidious_settings,
+                ))
+            })
+            .register(ytdl::INFO, |ctx| {
+                Box::new(ytdl::backend(ctx.cache, &ctx.settings()))
+            });
+
+        registry
+    }
+
+    /// Registers a back-end with its information and the factory that creates it.
+    ///
+    /// If a back-end with the same ID is already registered, it is replaced.
+    pub fn register<F>(&mut self, info: BackendInfo, factory: F) -> &mut Self
+    where
+        F: Fn(&BackendContext<'_>) -> Box<dyn Backend> + Send + Sync + 'static,
+    {
+        let factory: BackendFactory = Arc::new(factory);
+        match self
+            .entries
+            .iter_mut()
+            .find(|(other, _)| other.id == info.id)
+        {
+            Some(entry) => *entry = (info, factory),
+            None => self.entries.push((info, factory)),
+        }
+
+        self
+    }
+
+    /// Returns the information of the registered back-ends.
+    pub fn backends(&self) -> impl Iterator<Item = &BackendInfo> {
+        self.entries.iter().map(|(info, _)| info)
+    }
+
+    /// Returns the information of the registered back-ends that are enabled in the configuration.
+    pub(crate) fn enabled_backends<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> impl Iterator<Item = &'a BackendInfo> {
+        self.backends()
+            .filter(|info| config.backend(info.id).enabled)
+    }
+
+    /// Resolves the provided URL of a service to a back-end ID and a channel ID.
+    ///
+    /// Only the enabled back-ends of which a (built-in or configured) URL pattern matches the URL
+    /// are asked, so that no arbitrary hosts are probed. If no back-end recognises the URL,
+    /// [`None`] is returned.
+    pub(crate) async fn resolve(
+        &self,
+        url: &Url,
+        config: &Config,
+        cache: &Cache,
+    ) -> Result<Option<(&'static str, String)>> {
+        let matching = self
+            .enabled_backends(config)
+            .filter(|info| {
+                info.matches_url(url.as_str()) || config.backend(info.id).matches_url(url.as_str())
+            })
+            .collect::<Vec<_>>();
+        for info in matching {
+            let backend = self.get(info.id, config, cache)?;
+            if let Some(channel_id) = backend.resolve(url).await? {
+                return Ok(Some((info.id, channel_id)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Creates the back-end for the provided ID (if registered and enabled).
+    ///
+    /// The back-end will use the provided cache store to cache its results according to its
+    /// configuration.
+    pub(crate) fn get(
+        &self,
+        backend_id: &str,
+        config: &Config,
+        cache: &Cache,
+    ) -> Result<Box<dyn Backend>> {
+        let (_, factory) = self
+            .entries
+            .iter()
+            .find(|(info, _)| info.id == backend_id)
+            .ok_or_else(|| Error::UnsupportedBackend(backend_id.to_string()))?;
+        if !config.backend(backend_id).enabled {
+            return Err(Error::DisabledBackend(backend_id.to_string()));
+        }
+        let ctx = BackendContext {
+            id: backend_id,
+            config,
+            cache,
+        };
+
+        Ok(factory(&ctx))
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Registry")
+            .field("backends", &self.backends().collect::<Vec<_>>())
+            .finish()
     }
 }
 
-/// The supported back-ends.
-#[enum_dispatch(Backend)]
-pub(crate) enum Backends {
-    /// Mixcloud (<https://www.mixcloud.com>)
-    Mixcloud(mixcloud::Backend),
+/// The information of a back-end.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BackendInfo {
+    /// The ID of the back-end, as used in the feed and download URLs.
+    pub id: &'static str,
+
+    /// The (display) name of the back-end.
+    pub name: &'static str,
 
-    /// YouTube (<https://www.youtube.com>)
-    YouTube(youtube::Backend),
+    /// The description of the back-end, i.e. what its channel IDs refer to.
+    pub description: &'static str,
+
+    /// The patterns of the URLs of the service of the back-end.
+    ///
+    /// A pattern is a URL in which `*` matches any (possibly empty) sequence of characters.
+    pub url_patterns: &'static [&'static str],
+}
+
+impl BackendInfo {
+    /// Returns whether the provided URL matches one of the URL patterns of the back-end.
+    pub fn matches_url(&self, url: &str) -> bool {
+        self.url_patterns
+            .iter()
+            .any(|pattern| matches_pattern(pattern, url))
+    }
+}
+
+/// Returns whether the text matches the pattern in which `*` matches any sequence of characters.
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // There is no wildcard, so the text must match exactly.
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+/// The maximum number of redirects that are followed by the HTTP clients of the back-ends.
+const MAX_REDIRECTS: usize = 10;
+
+/// Returns whether content may be retrieved from the provided URL.
+///
+/// This is used by back-ends of which the service can be hosted anywhere. The URL is allowed if
+/// it matches one of the provided (configured) URL patterns or if its host is public, i.e. it is
+/// not (and does not resolve to) a loopback, private, link-local or otherwise internal address.
+///
+/// Note that the HTTP clients of the back-ends (see [`BackendConfig::http_client`]) check this
+/// again for redirects and only connect to the addresses they resolved and checked themselves.
+pub(crate) async fn is_allowed_url(url: &Url, url_patterns: &[String]) -> bool {
+    if url_patterns
+        .iter()
+        .any(|pattern| matches_pattern(pattern, url.as_str()))
+    {
+        return true;
+    }
+    if !is_public_host(url) {
+        return false;
+    }
+
+    let addrs = match url.host() {
+        Some(Host::Ipv4(addr)) => Vec::from([IpAddr::V4(addr)]),
+        Some(Host::Ipv6(addr)) => Vec::from([IpAddr::V6(addr)]),
+        Some(Host::Domain(domain)) => {
+            let port = url.port_or_known_default().unwrap_or_default();
+            match tokio::net::lookup_host((domain, port)).await {
+                Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+                Err(_) => return false,
+            }
+        }
+        None => return false,
+    };
+
+    !addrs.is_empty() && addrs.iter().all(is_public_ip)
+}
+
+/// Returns whether the provided URL is an HTTP(S) URL with a host that can be public.
+///
+/// IP addresses are checked directly, but host names are not resolved.
+fn is_public_host(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+
+    match url.host() {
+        Some(Host::Ipv4(addr)) => is_public_ip(&IpAddr::V4(addr)),
+        Some(Host::Ipv6(addr)) => is_public_ip(&IpAddr::V6(addr)),
+        Some(Host::Domain(domain)) => {
+            // Single-label hosts are not public, but refer to the local network.
+            let domain = domain.trim_end_matches('.');
+
+            domain.contains('.') && !domain.ends_with(".localhost")
+        }
+        None => false,
+    }
+}
+
+/// Returns the host (pattern) of the provided URL pattern (if any).
+fn pattern_host(pattern: &str) -> Option<&str> {
+    let (_scheme, rest) = pattern.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => host,
+        _ => authority,
+    };
+
+    Some(host)
+}
+
+/// A DNS resolver that only resolves host names to public IP addresses.
+///
+/// Host names that are allowed (by the host patterns) are resolved to any address. Because the
+/// HTTP clients connect to the addresses resolved here, the addresses that are connected to are
+/// always the checked ones.
+#[derive(Debug)]
+struct PublicResolver {
+    /// The patterns of the host names that may also resolve to addresses that are not public.
+    allowed_hosts: Vec<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let allowed = self
+            .allowed_hosts
+            .iter()
+            .any(|pattern| matches_pattern(pattern, &host));
+
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public_ip(&addr.ip()))
+                .collect::<Vec<SocketAddr>>();
+            if addrs.is_empty() {
+                let message = format!("{host} does not resolve to a public address");
+                return Err(
+                    std::io::Error::new(std::io::ErrorKind::PermissionDenied, message).into(),
+                );
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Returns whether the provided IP address is a public address.
+fn is_public_ip(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [first, second, ..] = addr.octets();
+            // The shared address space (100.64.0.0/10) is used for carrier-grade NAT.
+            let is_shared = first == 100 && (second & 0xc0) == 64;
+
+            !(addr.is_loopback()
+                || addr.is_private()
+                || addr.is_link_local()
+                || addr.is_unspecified()
+                || addr.is_broadcast()
+                || addr.is_documentation()
+                || is_shared)
+        }
+        IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+            Some(addr) => is_public_ip(&IpAddr::V4(addr)),
+            None => {
+                let first = addr.segments()[0];
+                // Unique local (fc00::/7) and link-local (fe80::/10) addresses are not public.
+                let is_unique_local = (first & 0xfe00) == 0xfc00;
+                let is_link_local = (first & 0xffc0) == 0xfe80;
+
+                !(addr.is_loopback() || addr.is_unspecified() || is_unique_local || is_link_local)
+            }
+        },
+    }
+}
+
+/// The context in which a back-end is created by its factory.
+#[derive(Debug)]
+pub struct BackendContext<'a> {
+    /// The ID of the back-end.
+    pub(crate) id: &'a str,
+
+    /// The application configuration.
+    pub(crate) config: &'a Config,
+
+    /// The cache store.
+    pub(crate) cache: &'a Cache,
+}
+
+impl BackendContext<'_> {
+    /// Returns the ID of the back-end that is being created.
+    pub fn id(&self) -> &str {
+        self.id
+    }
+
+    /// Returns the configuration of the back-end that is being created.
+    pub fn settings(&self) -> BackendConfig {
+        self.config.backend(self.id)
+    }
+
+    /// Returns the cache store that the back-end can use (see [`Cache::scoped`]).
+    pub fn cache(&self) -> &Cache {
+        self.cache
+    }
+}
+
+/// The configuration of a back-end.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct BackendConfig {
+    /// Whether the back-end is enabled.
+    pub(crate) enabled: bool,
+
+    /// The time-to-live configuration of the cached results of the back-end.
+    pub(crate) ttl: CacheTtls,
+
+    /// The way downloads of the back-end are served.
+    pub(crate) download: DownloadMode,
+
+    /// The format the media content of the back-end is transcoded to by default (if any).
+    pub(crate) format: Option<MediaFormat>,
+
+    /// Whether the media content of the back-end is archived.
+    pub(crate) archive: bool,
+
+    /// The upstream base URLs used by the back-end (by name), overriding the default ones.
+    pub(crate) base_urls: HashMap<String, Url>,
+
+    /// The timeout of HTTP requests to the upstream service (in seconds, if any).
+    pub(crate) timeout: Option<u64>,
+
+    /// The user agent used for HTTP requests to the upstream service (if any).
+    pub(crate) user_agent: Option<String>,
+
+    /// The additional URL patterns of the service of the back-end (see [`BackendInfo`]).
+    ///
+    /// For back-ends of which the service can be hosted anywhere, these are the hosts that are
+    /// allowed even if they are not public (see [`is_allowed_url`]).
+    pub(crate) url_patterns: Vec<String>,
+
+    /// Whether the transcripts of the items are included in the feeds (if available).
+    ///
+    /// Retrieving transcripts can be costly for some back-ends, so this is disabled by default.
+    pub(crate) transcripts: bool,
+
+    /// The languages of the transcripts that are included in the feeds by default.
+    ///
+    /// If empty, the transcripts in all available languages are included.
+    pub(crate) transcript_languages: Vec<String>,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl: Default::default(),
+            download: Default::default(),
+            format: Default::default(),
+            archive: Default::default(),
+            base_urls: Default::default(),
+            timeout: Default::default(),
+            user_agent: Default::default(),
+            url_patterns: Default::default(),
+            transcripts: Default::default(),
+            transcript_languages: Default::default(),
+        }
+    }
+}
+
+impl BackendConfig {
+    /// Returns whether the back-end is enabled.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the time-to-live configuration of the cached results of the back-end.
+    pub fn ttl(&self) -> CacheTtls {
+        self.ttl
+    }
+
+    /// Returns whether the provided URL matches one of the configured URL patterns.
+    pub fn matches_url(&self, url: &str) -> bool {
+        self.url_patterns
+            .iter()
+            .any(|pattern| matches_pattern(pattern, url))
+    }
+
+    /// Returns the configured upstream base URL with the provided name (if any).
+    pub fn base_url(&self, name: &str) -> Option<Url> {
+        self.base_urls.get(name).cloned()
+    }
+
+    /// Returns the configured upstream base URL with the provided name or the default one.
+    pub fn base_url_or(&self, name: &str, default: &str) -> Url {
+        self.base_url(name)
+            .unwrap_or_else(|| Url::parse(default).expect("valid URL"))
+    }
+
+    /// Returns whether the HTTP clients of the back-end may connect to the provided URL.
+    ///
+    /// This is the case if it matches one of the configured URL patterns, if its host is the
+    /// host of a configured upstream base URL or if its host can be public.
+    fn allows_url(&self, url: &Url) -> bool {
+        self.matches_url(url.as_str())
+            || self
+                .base_urls
+                .values()
+                .any(|base_url| base_url.host() == url.host())
+            || is_public_host(url)
+    }
+
+    /// Returns an HTTP client that uses the configured timeout and user agent.
+    ///
+    /// The client only connects to public hosts, including when following redirects, unless
+    /// they are allowed by the configured URL patterns or upstream base URLs.
+    pub fn http_client(&self) -> reqwest::Client {
+        let allowed_hosts = self
+            .url_patterns
+            .iter()
+            .filter_map(|pattern| pattern_host(pattern))
+            .chain(self.base_urls.values().filter_map(Url::host_str))
+            .map(String::from)
+            .collect();
+        let config = self.clone();
+        let redirect_policy = Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if config.allows_url(attempt.url()) {
+                attempt.follow()
+            } else {
+                let message = format!("redirect to non-public URL {}", attempt.url());
+                attempt.error(message)
+            }
+        });
+        let mut builder = reqwest::Client::builder()
+            .redirect(redirect_policy)
+            .dns_resolver(PublicResolver { allowed_hosts });
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+
+        builder.build().expect("HTTP client can be built")
+    }
+
+    /// Returns the options for `yt-dlp` that use the configured timeout and user agent.
+    pub(crate) fn youtube_dl_options(&self) -> YoutubeDlOptions {
+        YoutubeDlOptions {
+            timeout: self.timeout,
+            user_agent: self.user_agent.clone(),
+        }
+    }
+}
+
+/// The options for running `yt-dlp` on behalf of a back-end.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct YoutubeDlOptions {
+    /// The socket timeout (in seconds, if any).
+    timeout: Option<u64>,
+
+    /// The user agent (if any).
+    user_agent: Option<String>,
+}
+
+impl YoutubeDlOptions {
+    /// Returns a `yt-dlp` command for the provided URL using the options.
+    pub(crate) fn command(&self, url: impl Into<String>) -> YoutubeDl {
+        let mut command = YoutubeDl::new(url);
+        if let Some(timeout) = self.timeout {
+            command.socket_timeout(timeout.to_string());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            command.user_agent(user_agent);
+        }
+
+        command
+    }
 }
 
 /// Functionality of a content back-end.
+///
+/// Back-ends are registered in the [`Registry`].
 #[async_trait]
-#[enum_dispatch]
-pub(crate) trait Backend {
+pub trait Backend: Send + Sync {
     /// Returns the name of the backend.
-    #[allow(unused)] // For future use!
     fn name(&self) -> &'static str;
 
     /// Returns the channel with its currently contained content items.
@@ -49,86 +610,461 @@ pub(crate) trait Backend {
 
     /// Returns the redirect URL for the provided download file path.
     async fn redirect_url(&self, file: &Path) -> Result<String>;
+
+    /// Returns whether the media content needs to be transcoded to be playable by podcast clients.
+    ///
+    /// If so, the media content is transcoded to MP3 by default, unless another format is
+    /// configured for the back-end.
+    fn requires_transcoding(&self) -> bool {
+        false
+    }
+
+    /// Returns the channel ID for the provided URL of the service (if recognised).
+    ///
+    /// This is used to resolve the URLs that users copy from the service into feed URLs.
+    async fn resolve(&self, _url: &Url) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Returns the local content for the provided download file path (if any).
+    ///
+    /// Back-ends that have the media content available locally can return it so that it is
+    /// served directly instead of via the redirect URL.
+    async fn local_content(&self, _file: &Path) -> Result<Option<LocalContent>> {
+        Ok(None)
+    }
+
+    /// Returns the transcript in WebVTT format for the provided transcript file path (if any).
+    ///
+    /// See also [`Transcript::file`].
+    async fn transcript(&self, _file: &Path) -> Result<Option<String>> {
+        Ok(None)
+    }
 }
 
-/// The metadata of a collection of content items.
+/// Media content that is available locally.
 #[derive(Clone, Debug)]
-pub(crate) struct Channel {
+pub enum LocalContent {
+    /// A local file.
+    File(PathBuf),
+
+    /// In-memory data with its MIME type.
+    Data {
+        /// The MIME type of the data.
+        mime_type: String,
+
+        /// The data itself.
+        data: Vec<u8>,
+    },
+}
+
+/// The metadata of a collection of content items.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Channel {
     /// The title of the channel.
-    pub(crate) title: String,
+    pub title: String,
 
     /// The link to the channel.
-    pub(crate) link: Url,
+    pub link: Url,
 
     /// The description of the channel.
-    pub(crate) description: String,
+    pub description: String,
 
     /// The author/composer/creator of the channel.
-    pub(crate) author: Option<String>,
+    pub author: Option<String>,
 
     /// The categories associated with the channel.
     ///
     /// The first category is considered to be the "main" category.
-    pub(crate) categories: Vec<String>,
+    pub categories: Vec<String>,
 
     /// The URL of the image/logo/avatar of a channel.
-    pub(crate) image: Option<Url>,
+    pub image: Option<Url>,
+
+    /// The URLs of the image/logo/avatar of a channel in different sizes, by their width (in
+    /// pixels).
+    #[serde(default)]
+    pub images: BTreeMap<u32, Url>,
+
+    /// The medium of the content items of the channel.
+    #[serde(default)]
+    pub medium: Medium,
 
     /// The contained content items.
-    pub(crate) items: Vec<Item>,
+    pub items: Vec<Item>,
+}
+
+/// The medium of the content items of a channel.
+///
+/// See also the [`podcast:medium`](https://podcasting2.org/podcast-namespace/tags/medium) tag.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Medium {
+    /// Spoken word content, the default.
+    #[default]
+    Podcast,
+
+    /// Music, such as albums, tracks or mixes.
+    Music,
+
+    /// Video content.
+    Video,
+
+    /// Film content.
+    Film,
+
+    /// Audiobook content.
+    Audiobook,
+
+    /// Newsletter content (read out loud).
+    Newsletter,
+
+    /// Blog content (read out loud).
+    Blog,
+}
+
+impl Medium {
+    /// Returns the medium as string, as used in feeds.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Medium::Podcast => "podcast",
+            Medium::Music => "music",
+            Medium::Video => "video",
+            Medium::Film => "film",
+            Medium::Audiobook => "audiobook",
+            Medium::Newsletter => "newsletter",
+            Medium::Blog => "blog",
+        }
+    }
+
+    /// Parses the medium from a string as used in feeds, if known.
+    pub fn parse(medium: &str) -> Option<Self> {
+        [
+            Medium::Podcast,
+            Medium::Music,
+            Medium::Video,
+            Medium::Film,
+            Medium::Audiobook,
+            Medium::Newsletter,
+            Medium::Blog,
+        ]
+        .into_iter()
+        .find(|known| known.as_str() == medium.trim())
+    }
 }
 
 /// A content item belonging to a channel.
-#[derive(Clone, Debug)]
-pub(crate) struct Item {
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Item {
     /// The title of the item.
-    pub(crate) title: String,
+    pub title: String,
 
     /// The direct link to the item.
-    pub(crate) link: Url,
+    pub link: Url,
 
     /// The description of the item.
-    pub(crate) description: Option<String>,
+    pub description: Option<String>,
 
-    /// The categories of the items (and their domain URLs).
-    pub(crate) categories: HashMap<String, Url>,
+    /// The categories of the items (and their domain URLs), ordered by name.
+    pub categories: BTreeMap<String, Url>,
 
     /// The enclosed media content of the item,
-    pub(crate) enclosure: Enclosure,
+    pub enclosure: Enclosure,
 
     /// The duration of the media content (in seconds).
-    pub(crate) duration: Option<u32>,
+    pub duration: Option<u32>,
+
+    /// The chapters of the media content (if known), in order of their start time.
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+
+    /// The transcripts of the media content (if any).
+    #[serde(default)]
+    pub transcripts: Vec<Transcript>,
 
     /// The global UID of the item.
     ///
     /// This GUID is not considered nor needs to be a permalink.
-    pub(crate) guid: String,
+    pub guid: String,
 
     /// The keywords associated with the item.
-    pub(crate) keywords: Vec<String>,
+    pub keywords: Vec<String>,
+
+    /// The season the item belongs to (if known).
+    #[serde(default)]
+    pub season: Option<u32>,
+
+    /// The episode number of the item (within its season, if known).
+    #[serde(default)]
+    pub episode: Option<u32>,
 
     /// The URL of the image of the item.
-    pub(crate) image: Option<Url>,
+    pub image: Option<Url>,
 
     /// The timestamp the item was published.
-    pub(crate) published_at: DateTime<Utc>,
+    pub published_at: DateTime<Utc>,
 
     /// The timestamp the item was last updated.
-    pub(crate) updated_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A chapter of the media content of an item.
+///
+/// It serializes the same as a chapter in the
+/// [JSON chapters format](https://github.com/Podcastindex-org/podcast-namespace/blob/main/chapters/jsonChapters.md).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub struct Chapter {
+    /// The start time of the chapter (in seconds).
+    pub start_time: u32,
+
+    /// The title of the chapter.
+    pub title: String,
+}
+
+/// A transcript of the media content of an item.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Transcript {
+    /// The language (code) of the transcript.
+    pub language: String,
+
+    /// The path of the transcript file (in WebVTT format).
+    ///
+    /// This is used as a part of the transcript URLs of the item and will be passed to
+    /// [`Backend::transcript`] later when a client wants to retrieve the transcript.
+    pub file: PathBuf,
 }
 
 /// The enclosed media content of an item.
-#[derive(Clone, Debug)]
-pub(crate) struct Enclosure {
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Enclosure {
     /// The path of the download file associated with the item enclosure.
     ///
     /// This is used as a part of the enclosure URL of the item and will be passed to
     /// [`Backend::redirect_url`] later when a client wants to download the media content.
-    pub(crate) file: PathBuf,
+    pub file: PathBuf,
 
     /// The MIME type of the download file path associated with the item enclosure.
-    pub(crate) mime_type: String,
+    pub mime_type: String,
 
     /// The length of the enclosed media content (in bytes).
-    pub(crate) length: u64,
+    pub length: u64,
+}
+
+/// Returns the non-empty path segments of the provided URL.
+pub(crate) fn path_segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the file extension for the provided MIME type (if known).
+///
+/// This prefers the extensions that podcast clients recognise, e.g. `mp3` for `audio/mpeg`
+/// where [`mime_db::extension`] returns `mpga`.
+pub(crate) fn mime_extension(mime_type: &str) -> Option<&'static str> {
+    match mime_type {
+        "audio/mpeg" => Some("mp3"),
+        _ => mime_db::extension(mime_type),
+    }
+}
+
+/// The category of channels for which the upstream service provides no categories.
+pub(crate) const DEFAULT_CATEGORY: &str = "Podcast";
+
+/// Returns the channel categories for the provided (item) categories.
+///
+/// Duplicates are removed and the categories are ordered by how often they occur, so that the
+/// most common one becomes the main category. If there are none, [`DEFAULT_CATEGORY`] is used.
+pub(crate) fn channel_categories(categories: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut counts = Vec::<(String, usize)>::new();
+    for category in categories {
+        let category = category.trim();
+        if category.is_empty() {
+            continue;
+        }
+        match counts.iter_mut().find(|(name, _)| name == category) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((String::from(category), 1)),
+        }
+    }
+    if counts.is_empty() {
+        return Vec::from([String::from(DEFAULT_CATEGORY)]);
+    }
+    // The sort is stable, so categories that occur equally often keep their order.
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    counts.into_iter().map(|(name, _)| name).collect()
+}
+
+/// The maximum length of a path component of a download file path that encodes a URL.
+const MAX_URL_FILE_COMPONENT_LEN: usize = 200;
+
+/// Returns the download file path that encodes the provided URL with the provided extension.
+///
+/// This can be used by back-ends that identify their media content by a URL instead of some ID.
+/// The URL is hex-encoded and split into multiple path components if it is long, so that the file
+/// path can also be used for storage (see [`crate::archive`]).
+pub(crate) fn url_file(url: &str, extension: &str) -> PathBuf {
+    let encoded = url
+        .bytes()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let mut file = encoded
+        .as_bytes()
+        .chunks(MAX_URL_FILE_COMPONENT_LEN)
+        .map(|chunk| std::str::from_utf8(chunk).expect("hex is valid UTF-8"))
+        .collect::<PathBuf>();
+    file.set_extension(extension);
+
+    file
+}
+
+/// Returns the HTTP(S) URL encoded in the provided download file path (see [`url_file`]).
+///
+/// If the file path does not encode a valid URL with an `http` or `https` scheme, [`None`] is
+/// returned. The URL is passed on to external tools (e.g. yt-dlp or ffmpeg), so anything else,
+/// such as local paths or command-line options, must be rejected.
+pub(crate) fn file_url(file: &Path) -> Option<Url> {
+    let encoded = file
+        .with_extension("")
+        .iter()
+        .map(|component| component.to_str())
+        .collect::<Option<String>>()?;
+    if encoded.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..encoded.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(encoded.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let url = Url::parse(&String::from_utf8(bytes).ok()?).ok()?;
+
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rocket::async_test]
+    async fn is_allowed_url_rejects_internal_hosts() {
+        for url in [
+            "http://127.0.0.1/feed.xml",
+            "http://10.1.2.3/feed.xml",
+            "http://192.168.1.1:8080/feed.xml",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/feed.xml",
+            "http://0.0.0.0/feed.xml",
+            "http://[::1]/feed.xml",
+            "http://[fd00::1]/feed.xml",
+            "http://[fe80::1]/feed.xml",
+            "http://[::ffff:127.0.0.1]/feed.xml",
+            "http://localhost:7099/feed.xml",
+            "http://feeds.localhost/feed.xml",
+            "http://router/feed.xml",
+        ] {
+            let url = Url::parse(url).unwrap();
+            assert!(!is_allowed_url(&url, &[]).await, "{url} is allowed");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn is_allowed_url_accepts_public_and_configured_hosts() {
+        let url = Url::parse("https://93.184.215.14/feed.xml").unwrap();
+        assert!(is_allowed_url(&url, &[]).await);
+
+        let url = Url::parse("file:///etc/passwd").unwrap();
+        assert!(!is_allowed_url(&url, &[]).await);
+
+        let url = Url::parse("http://192.168.1.1:8080/feed.xml").unwrap();
+        let url_patterns = [String::from("http://192.168.1.1:8080/*")];
+        assert!(is_allowed_url(&url, &url_patterns).await);
+    }
+
+    #[test]
+    fn pattern_host_strips_scheme_port_and_path() {
+        assert_eq!(
+            pattern_host("https://*.tube.example.org/*"),
+            Some("*.tube.example.org")
+        );
+        assert_eq!(
+            pattern_host("http://192.168.1.1:8080/*"),
+            Some("192.168.1.1")
+        );
+        assert_eq!(pattern_host("http://[fd00::1]/*"), Some("[fd00::1]"));
+        assert_eq!(pattern_host("feeds.example.org"), None);
+    }
+
+    #[test]
+    fn config_refuses_urls_of_internal_hosts() {
+        let config = BackendConfig::default();
+        for url in [
+            "http://127.0.0.1/feed.xml",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://localhost/feed.xml",
+            "file:///etc/passwd",
+        ] {
+            let url = Url::parse(url).unwrap();
+            assert!(!config.allows_url(&url), "{url} is allowed");
+        }
+
+        let config = BackendConfig {
+            url_patterns: Vec::from([String::from("http://192.168.1.1:8080/*")]),
+            ..Default::default()
+        };
+        let url = Url::parse("http://192.168.1.1:8080/feed.xml").unwrap();
+        assert!(config.allows_url(&url));
+    }
+
+    #[rocket::async_test]
+    async fn public_resolver_refuses_internal_addresses() {
+        let resolver = PublicResolver {
+            allowed_hosts: Vec::new(),
+        };
+        let name = "localhost".parse().unwrap();
+        assert!(resolver.resolve(name).await.is_err());
+
+        let resolver = PublicResolver {
+            allowed_hosts: Vec::from([String::from("localhost")]),
+        };
+        let name = "localhost".parse().unwrap();
+        assert!(resolver.resolve(name).await.is_ok());
+    }
+
+    #[test]
+    fn mime_extension_prefers_common_extensions() {
+        assert_eq!(mime_extension("audio/mpeg"), Some("mp3"));
+        assert_eq!(mime_extension("audio/mp4"), Some("m4a"));
+        assert_eq!(mime_extension("video/mp4"), Some("mp4"));
+        assert_eq!(mime_extension("application/x-unknown"), None);
+    }
+
+    #[test]
+    fn channel_categories_orders_by_occurrence() {
+        let categories = [
+            "Techno", "House", " ", "House", "Ambient", "House", "Techno",
+        ];
+        assert_eq!(
+            channel_categories(categories.map(String::from)),
+            ["House", "Techno", "Ambient"]
+        );
+        assert_eq!(channel_categories(Vec::new()), [DEFAULT_CATEGORY]);
+    }
+
+    #[test]
+    fn config_matches_url() {
+        let config = BackendConfig {
+            url_patterns: Vec::from([String::from("https://peertube.example.org/*")]),
+            ..Default::default()
+        };
+
+        assert!(config.matches_url("https://peertube.example.org/c/channel"));
+        assert!(!config.matches_url("https://other.example.org/c/channel"));
+    }
 }
@@ -0,0 +1,606 @@
+//! The Bandcamp back-end.
+//!
+//! Bandcamp has no (public) API, so it scrapes the music page of an artist or label to retrieve
+//! the feed and the pages of its releases (albums and tracks) to retrieve the items (the freely
+//! streamable tracks). The metadata of a release is embedded in its page as JSON (`data-tralbum`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::Url;
+use rocket::serde::json::from_str;
+use rocket::serde::{Deserialize, Serialize};
+
+use super::{
+    channel_categories, mime_extension, BackendConfig, BackendInfo, Channel, Enclosure, Item,
+    Medium,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::transcript::unescape_entities;
+use crate::{Error, Result};
+
+/// The domain of all Bandcamp artist and label pages.
+const BANDCAMP_DOMAIN: &str = "bandcamp.com";
+
+/// The base URL for Bandcamp images.
+const IMAGE_BASE_URL: &str = "https://f4.bcbits.com/img";
+
+/// The format used by Bandcamp for dates in the release metadata.
+const DATE_FORMAT: &str = "%d %b %Y %H:%M:%S GMT";
+
+/// The (only) stream format of freely streamable tracks.
+const STREAM_FORMAT: &str = "mp3-128";
+
+/// The bitrate of the streams used by Bandcamp.
+const DEFAULT_BITRATE: u64 = 128 * 1024;
+
+/// The file (MIME) type of the streams used by Bandcamp.
+const DEFAULT_FILE_TYPE: &str = "audio/mpeg";
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The information of the Bandcamp back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "bandcamp",
+    name: "Bandcamp",
+    description: "Bandcamp artist or label subdomain",
+    url_patterns: &["https://*.bandcamp.com/*"],
+};
+
+/// Creates a Bandcamp back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        cache: cache.scoped("bandcamp", settings.ttl),
+    }
+}
+
+/// The Bandcamp back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The cache store used for discographies, releases and redirect URLs.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "Bandcamp"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For Bandcamp a channel ID is the subdomain of an artist or label.
+        if !is_valid_subdomain(channel_id) {
+            return Err(Error::UnsupportedChannel(channel_id.to_string()));
+        }
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+        let music_url = Url::parse(&format!("https://{channel_id}.{BANDCAMP_DOMAIN}/music"))?;
+
+        println!("⏬ Retrieving discography of {channel_id} from {music_url}...");
+        let discography = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                music_url.as_str(),
+                fetch_discography(self.client.clone(), music_url.clone()),
+            )
+            .await?;
+
+        // The items of a channel are the streamable tracks of the releases.
+        let mut releases = Vec::new();
+        let mut track_count = 0;
+        for release_url in &discography.releases {
+            if track_count >= limit {
+                break;
+            }
+
+            println!("⏬ Retrieving release from {release_url}...");
+            let release: Release = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    release_url.as_str(),
+                    fetch_release(self.client.clone(), release_url.clone()),
+                )
+                .await?;
+            track_count += release.tracks.len();
+            releases.push(release);
+        }
+
+        let mut channel = Channel::from(DiscographyWithReleases(discography, releases));
+        channel.items.truncate(limit);
+
+        Ok(channel)
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let (release_url, track_id) = download_key(file)?;
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                &file.to_string_lossy(),
+                retrieve_redirect_url(self.client.clone(), release_url, track_id),
+            )
+            .await
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // The subdomain of an artist or label URL is the channel ID.
+        let subdomain = url
+            .host_str()
+            .and_then(|host| host.strip_suffix(BANDCAMP_DOMAIN)?.strip_suffix('.'))
+            .filter(|subdomain| is_valid_subdomain(subdomain));
+
+        Ok(subdomain.map(String::from))
+    }
+}
+
+/// A Bandcamp discography with its releases.
+pub(crate) struct DiscographyWithReleases(Discography, Vec<Release>);
+
+/// The discography of a Bandcamp artist or label (scraped from its music page).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Discography {
+    /// The name of the artist or label.
+    pub(crate) name: String,
+
+    /// The description of the artist or label.
+    pub(crate) description: Option<String>,
+
+    /// The URL of the image of the artist or label.
+    pub(crate) image: Option<Url>,
+
+    /// The original URL of the artist or label.
+    pub(crate) url: Url,
+
+    /// The URLs of the releases (albums and tracks), newest first.
+    pub(crate) releases: Vec<Url>,
+}
+
+/// A Bandcamp release, i.e. an album or a single track (scraped from its page).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Release {
+    /// The title of the release.
+    pub(crate) title: String,
+
+    /// The artist of the release.
+    pub(crate) artist: String,
+
+    /// The description of the release (including the credits).
+    pub(crate) description: Option<String>,
+
+    /// The URL of the artwork of the release.
+    pub(crate) image: Option<Url>,
+
+    /// The original URL of the release.
+    pub(crate) url: Url,
+
+    /// The time the release was released.
+    pub(crate) released_at: DateTime<Utc>,
+
+    /// The time the release was last modified.
+    pub(crate) updated_at: DateTime<Utc>,
+
+    /// The tags (genres, locations, etc.) of the release with their URLs, in order of appearance.
+    #[serde(default)]
+    pub(crate) tags: Vec<(String, Url)>,
+
+    /// The freely streamable tracks of the release.
+    pub(crate) tracks: Vec<Track>,
+}
+
+/// A freely streamable track of a Bandcamp release.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Track {
+    /// The ID of the track.
+    pub(crate) id: u64,
+
+    /// The title of the track.
+    pub(crate) title: String,
+
+    /// The original URL of the track.
+    pub(crate) url: Url,
+
+    /// The duration of the track (in seconds).
+    pub(crate) duration: u32,
+
+    /// The URL of the stream of the track.
+    ///
+    /// Note that this URL expires after a while.
+    pub(crate) stream_url: String,
+}
+
+/// The metadata of a Bandcamp release as embedded in its page.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct TrAlbum {
+    /// The artist of the release.
+    artist: String,
+
+    /// The ID of the artwork of the release.
+    art_id: Option<u64>,
+
+    /// The release date of the album the release belongs to (for tracks).
+    album_release_date: Option<String>,
+
+    /// The current metadata of the release.
+    current: TrAlbumCurrent,
+
+    /// The tracks of the release.
+    trackinfo: Vec<TrackInfo>,
+}
+
+/// The current metadata of a Bandcamp release.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct TrAlbumCurrent {
+    /// The title of the release.
+    title: String,
+
+    /// The description of the release.
+    about: Option<String>,
+
+    /// The credits of the release.
+    credits: Option<String>,
+
+    /// The release date of the release.
+    release_date: Option<String>,
+
+    /// The publish date of the release.
+    publish_date: Option<String>,
+
+    /// The last modification date of the release.
+    mod_date: Option<String>,
+}
+
+/// The metadata of a track of a Bandcamp release.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct TrackInfo {
+    /// The ID of the track.
+    track_id: Option<u64>,
+
+    /// The ID of the track (for single track releases).
+    id: Option<u64>,
+
+    /// The title of the track.
+    title: String,
+
+    /// The (relative) link to the track.
+    title_link: Option<String>,
+
+    /// The duration of the track (in seconds).
+    duration: Option<f64>,
+
+    /// The stream URLs of the track per format (if freely streamable).
+    file: Option<HashMap<String, String>>,
+}
+
+/// A music grid item of a Bandcamp music page.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ClientItem {
+    /// The (relative) URL of the release.
+    page_url: String,
+}
+
+impl From<DiscographyWithReleases> for Channel {
+    fn from(DiscographyWithReleases(discography, releases): DiscographyWithReleases) -> Self {
+        let tags = releases
+            .iter()
+            .flat_map(|release| release.tags.iter().map(|(tag, _)| tag.clone()))
+            .collect::<Vec<_>>();
+        let categories = channel_categories(tags);
+        let items = releases
+            .into_iter()
+            .flat_map(|release| {
+                let tracks = release.tracks.clone();
+                tracks.into_iter().map(move |track| {
+                    Item::from(ReleaseTrack {
+                        release: release.clone(),
+                        track,
+                    })
+                })
+            })
+            .collect();
+
+        Channel {
+            title: format!("{0} (via Bandcamp)", discography.name),
+            link: discography.url,
+            description: discography.description.unwrap_or_default(),
+            author: Some(discography.name),
+            categories,
+            image: discography.image,
+            images: BTreeMap::new(),
+            medium: Medium::Music,
+            items,
+        }
+    }
+}
+
+/// A track of a Bandcamp release.
+struct ReleaseTrack {
+    /// The release the track belongs to.
+    release: Release,
+
+    /// The track.
+    track: Track,
+}
+
+impl From<ReleaseTrack> for Item {
+    fn from(ReleaseTrack { release, track }: ReleaseTrack) -> Self {
+        let mut file = PathBuf::from(release.url.host_str().unwrap_or_default());
+        file.push(release.url.path().trim_matches('/'));
+        file.push(track.id.to_string());
+        let extension = mime_extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
+        file.set_extension(extension);
+
+        let title = if release.tracks.len() > 1 {
+            format!("{} ({})", track.title, release.title)
+        } else {
+            track.title
+        };
+        let description = release
+            .description
+            .unwrap_or_else(|| format!("Taken from Bandcamp: {0}", release.url));
+        let enclosure = Enclosure {
+            file,
+            mime_type: String::from(DEFAULT_FILE_TYPE),
+            length: estimated_file_size(track.duration),
+        };
+
+        Item {
+            title,
+            link: track.url,
+            description: Some(description),
+            categories: release.tags.into_iter().collect(),
+            enclosure,
+            duration: Some(track.duration),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            guid: track.id.to_string(),
+            keywords: Vec::from([release.artist]),
+            season: None,
+            episode: None,
+            image: release.image,
+            published_at: release.released_at,
+            updated_at: release.updated_at,
+        }
+    }
+}
+
+/// Returns the release URL and track ID for the provided download file path.
+///
+/// Only releases on Bandcamp domains are supported.
+fn download_key(file: &Path) -> Result<(Url, u64)> {
+    let unsupported = || Error::UnsupportedChannel(file.to_string_lossy().into_owned());
+    let track_id = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+        .ok_or_else(unsupported)?;
+    let release_path = file.parent().ok_or_else(unsupported)?;
+    let release_url = Url::parse(&format!("https://{}", release_path.to_string_lossy()))?;
+    if !release_url
+        .host_str()
+        .is_some_and(|host| host.ends_with(&format!(".{BANDCAMP_DOMAIN}")))
+    {
+        return Err(unsupported());
+    }
+
+    Ok((release_url, track_id))
+}
+
+/// Returns the estimated file size in bytes for a given duration.
+///
+/// It uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
+fn estimated_file_size(duration: u32) -> u64 {
+    DEFAULT_BITRATE * duration as u64 / 8
+}
+
+/// Parses a date in the Bandcamp release metadata.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(date, DATE_FORMAT)
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+/// Returns the (unescaped) value of the first occurrence of the attribute in the HTML.
+fn attribute(html: &str, name: &str) -> Option<String> {
+    let (_, rest) = html.split_once(&format!(" {name}=\""))?;
+    let (value, _) = rest.split_once('"')?;
+
+    Some(unescape_entities(value))
+}
+
+/// Returns the (unescaped) content of the meta tag with the provided property in the HTML.
+fn meta_content(html: &str, property: &str) -> Option<String> {
+    let (_, rest) = html.split_once(&format!("property=\"{property}\""))?;
+    let (tag, _) = rest.split_once('>')?;
+
+    attribute(tag, "content")
+}
+
+/// Returns the (unescaped) names and URLs of the tags linked to in the HTML.
+fn tags(html: &str) -> Vec<(String, Url)> {
+    html.split("<a class=\"tag\"")
+        .skip(1)
+        .filter_map(|part| {
+            let (tag, rest) = part.split_once('>')?;
+            let (name, _) = rest.split_once('<')?;
+            let url = Url::parse(&attribute(tag, "href")?).ok()?;
+
+            Some((unescape_entities(name.trim()), url))
+        })
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+/// Returns whether the subdomain is a valid Bandcamp artist or label subdomain.
+fn is_valid_subdomain(subdomain: &str) -> bool {
+    !subdomain.is_empty()
+        && subdomain != "www"
+        && subdomain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Fetches the HTML of the page at the URL.
+///
+/// The final URL (after redirects) is returned as well.
+async fn fetch_page(client: reqwest::Client, url: Url) -> Result<(Url, String)> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    let url = response.url().clone();
+    let html = response.text().await?;
+
+    Ok((url, html))
+}
+
+/// Fetches the discography from the music page URL.
+///
+/// If the artist has only a single release, the music page redirects to its page.
+async fn fetch_discography(client: reqwest::Client, url: Url) -> Result<Discography> {
+    let (url, html) = fetch_page(client, url).await?;
+    let name = meta_content(&html, "og:site_name")
+        .or_else(|| meta_content(&html, "og:title"))
+        .ok_or_else(|| Error::UnsupportedChannel(url.to_string()))?;
+    let description = meta_content(&html, "og:description").filter(|descr| !descr.is_empty());
+    let image = meta_content(&html, "og:image").and_then(|image| Url::parse(&image).ok());
+
+    let releases = if html.contains("data-tralbum=\"") {
+        Vec::from([url.clone()])
+    } else {
+        // The music grid lists the first releases as links, but all releases are listed as JSON
+        // if there are many.
+        let grid = html
+            .split_once("id=\"music-grid\"")
+            .map(|(_, grid)| grid)
+            .unwrap_or_default();
+        let client_items = attribute(grid, "data-client-items")
+            .and_then(|items| from_str::<Vec<ClientItem>>(&items).ok())
+            .unwrap_or_default();
+        let release_paths = if client_items.is_empty() {
+            grid.split(" href=\"")
+                .skip(1)
+                .filter_map(|part| {
+                    part.split_once('"')
+                        .map(|(href, _)| unescape_entities(href))
+                })
+                .filter(|href| href.contains("/album/") || href.contains("/track/"))
+                .collect::<Vec<_>>()
+        } else {
+            client_items.into_iter().map(|item| item.page_url).collect()
+        };
+
+        let mut releases = Vec::<Url>::new();
+        for path in release_paths {
+            let release_url = url.join(&path)?;
+            if !releases.contains(&release_url) {
+                releases.push(release_url);
+            }
+        }
+        releases
+    };
+    let mut url = url;
+    url.set_path("/");
+
+    Ok(Discography {
+        name,
+        description,
+        image,
+        url,
+        releases,
+    })
+}
+
+/// Fetches the release from the release page URL.
+async fn fetch_release(client: reqwest::Client, url: Url) -> Result<Release> {
+    let (url, html) = fetch_page(client, url).await?;
+    let tags = tags(&html);
+    let tralbum = attribute(&html, "data-tralbum").ok_or(Error::NoRedirectUrlFound)?;
+    let tralbum: TrAlbum = from_str(&tralbum)?;
+
+    let current = tralbum.current;
+    // Fall back to a fixed timestamp for undated releases, so that the feed stays stable.
+    let released_at = current
+        .release_date
+        .or(tralbum.album_release_date)
+        .or_else(|| current.publish_date.clone())
+        .or_else(|| current.mod_date.clone())
+        .as_deref()
+        .and_then(parse_date)
+        .unwrap_or(DateTime::UNIX_EPOCH);
+    let updated_at = current
+        .mod_date
+        .as_deref()
+        .and_then(parse_date)
+        .unwrap_or(released_at);
+    let description = [current.about, current.credits]
+        .into_iter()
+        .flatten()
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>();
+    let description = (!description.is_empty()).then(|| description.join("\n\n"));
+    let image = tralbum
+        .art_id
+        .and_then(|art_id| Url::parse(&format!("{IMAGE_BASE_URL}/a{art_id:010}_10.jpg")).ok());
+    let tracks = tralbum
+        .trackinfo
+        .into_iter()
+        .filter_map(|track_info| {
+            let stream_url = track_info.file?.remove(STREAM_FORMAT)?;
+            let track_url = match track_info.title_link {
+                Some(link) => url.join(&link).ok()?,
+                None => url.clone(),
+            };
+
+            Some(Track {
+                id: track_info.track_id.or(track_info.id)?,
+                title: track_info.title,
+                url: track_url,
+                duration: track_info.duration.unwrap_or_default() as u32,
+                stream_url,
+            })
+        })
+        .collect();
+
+    Ok(Release {
+        title: current.title,
+        artist: tralbum.artist,
+        description,
+        image,
+        url,
+        released_at,
+        updated_at,
+        tags,
+        tracks,
+    })
+}
+
+/// Retrieves the redirect URL for the provided track of the Bandcamp release.
+///
+/// Since the stream URLs expire, the release is always retrieved again.
+async fn retrieve_redirect_url(
+    client: reqwest::Client,
+    release_url: Url,
+    track_id: u64,
+) -> Result<String> {
+    println!("🌍 Determining direct URL for track {track_id} of {release_url}...");
+    let release = fetch_release(client, release_url).await?;
+
+    release
+        .tracks
+        .into_iter()
+        .find(|track| track.id == track_id)
+        .map(|track| track.stream_url)
+        .ok_or(Error::NoRedirectUrlFound)
+}
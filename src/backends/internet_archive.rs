@@ -0,0 +1,508 @@
+//! The Internet Archive back-end.
+//!
+//! It uses the advanced search API of the Internet Archive to retrieve the items of the feed
+//! (collection or uploader) and the metadata API to retrieve the metadata and files of the
+//! collection and the items.
+//! See also: <https://archive.org/developers/>
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use reqwest::Url;
+use rocket::futures::{stream, StreamExt};
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+
+use super::{
+    channel_categories, path_segments, BackendConfig, BackendInfo, Channel, Enclosure, Item, Medium,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::chapters;
+use crate::{Error, Result};
+
+/// The base URL of the Internet Archive.
+const BASE_URL: &str = "https://archive.org";
+
+/// The prefix of channel IDs that refer to uploaders.
+const UPLOADER_PREFIX: &str = "uploader:";
+
+/// The file formats of the audio derivatives, in order of preference.
+const PREFERRED_FORMATS: [&str; 4] = ["VBR MP3", "128Kbps MP3", "64Kbps MP3", "Ogg Vorbis"];
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The maximum number of concurrent requests for the metadata of items.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// The information of the Internet Archive back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "archive",
+    name: "Internet Archive",
+    description:
+        "collection identifier or uploader e-mail address like uploader:someone@example.org",
+    url_patterns: &["https://archive.org/details/*"],
+};
+
+/// Creates an Internet Archive back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        base_url: settings.base_url_or("website", BASE_URL),
+        cache: cache.scoped("archive", settings.ttl),
+    }
+}
+
+/// The Internet Archive back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The base URL of the Internet Archive (for its APIs and downloading files).
+    base_url: Url,
+
+    /// The cache store used for collections, search results and item metadata.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "Internet Archive"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For the Internet Archive a channel ID is either the identifier of a collection or the
+        // (e-mail address of an) uploader prefixed with `uploader:`.
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+        let (mut channel, query) = match channel_id.strip_prefix(UPLOADER_PREFIX) {
+            Some(uploader) if is_valid_uploader(uploader) => {
+                (uploader_channel(uploader), format!("uploader:({uploader})"))
+            }
+            None if is_valid_identifier(channel_id) => {
+                let metadata_url = metadata_url(&self.base_url, channel_id);
+                println!("⏬ Retrieving collection {channel_id} from {metadata_url}...");
+                let collection: Metadata = self
+                    .cache
+                    .get_or_fetch(
+                        CacheKind::Channel,
+                        metadata_url.as_str(),
+                        fetch(self.client.clone(), metadata_url.clone()),
+                    )
+                    .await?;
+                if collection.metadata.is_none() {
+                    return Err(Error::UnsupportedChannel(channel_id.to_string()));
+                }
+
+                (
+                    Channel::try_from(collection)?,
+                    format!("collection:({channel_id})"),
+                )
+            }
+            _ => return Err(Error::UnsupportedChannel(channel_id.to_string())),
+        };
+
+        // The items of a channel are the (non-collection) items found by the search query.
+        let mut search_url = self.base_url.clone();
+        search_url.set_path("advancedsearch.php");
+        search_url
+            .query_pairs_mut()
+            .append_pair("q", &format!("{query} AND -mediatype:(collection)"))
+            .append_pair("fl[]", "identifier")
+            .append_pair("sort[]", "publicdate desc")
+            .append_pair("rows", &limit.to_string())
+            .append_pair("page", "1")
+            .append_pair("output", "json");
+        println!("⏬ Retrieving items of {channel_id} from {search_url}...");
+        let search_res: SearchResponse = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Items,
+                search_url.as_str(),
+                fetch(self.client.clone(), search_url.clone()),
+            )
+            .await?;
+
+        // The metadata of the items is retrieved concurrently (but in order). Items of which the
+        // metadata cannot be retrieved are skipped, like items without usable files.
+        let items = stream::iter(search_res.response.docs)
+            .map(|doc| async move {
+                let metadata_url = metadata_url(&self.base_url, &doc.identifier);
+                let metadata = self
+                    .cache
+                    .get_or_fetch::<Metadata, _>(
+                        CacheKind::Items,
+                        metadata_url.as_str(),
+                        fetch(self.client.clone(), metadata_url.clone()),
+                    )
+                    .await;
+
+                match metadata {
+                    Ok(metadata) => Item::try_from(metadata).ok(),
+                    Err(err) => {
+                        eprintln!(
+                            "⚠️ Retrieving item {} failed: {err}, skipping...",
+                            doc.identifier
+                        );
+                        None
+                    }
+                }
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .filter_map(|item| async move { item })
+            .collect::<Vec<_>>()
+            .await;
+        channel.items.extend(items);
+        if channel.categories.is_empty() {
+            let subjects = channel.items.iter().flat_map(|item| item.keywords.clone());
+            channel.categories = channel_categories(subjects);
+        }
+
+        Ok(channel)
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("URL can be a base")
+            .push("download")
+            .extend(file.iter().map(|component| component.to_string_lossy()));
+
+        Ok(url.to_string())
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // The identifier in a collection URL (`details/identifier`) is the channel ID.
+        let identifier = match path_segments(url)[..] {
+            ["details", identifier, ..] if is_valid_identifier(identifier) => Some(identifier),
+            _ => None,
+        };
+
+        Ok(identifier.map(String::from))
+    }
+}
+
+/// The Internet Archive advanced search response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct SearchResponse {
+    /// The actual response.
+    response: SearchResults,
+}
+
+/// The Internet Archive advanced search results.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct SearchResults {
+    /// The found documents.
+    docs: Vec<SearchDoc>,
+}
+
+/// An Internet Archive advanced search document.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct SearchDoc {
+    /// The identifier of the found item.
+    identifier: String,
+}
+
+/// The Internet Archive metadata of an item or collection (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Metadata {
+    /// The metadata fields.
+    ///
+    /// This is absent if the item or collection does not exist.
+    pub(crate) metadata: Option<MetadataFields>,
+
+    /// The files of the item.
+    #[serde(default)]
+    pub(crate) files: Vec<File>,
+}
+
+/// The Internet Archive metadata fields of an item or collection.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct MetadataFields {
+    /// The identifier of the item or collection.
+    pub(crate) identifier: String,
+
+    /// The title of the item or collection.
+    pub(crate) title: Option<OneOrMany>,
+
+    /// The description of the item or collection (may contain HTML).
+    pub(crate) description: Option<OneOrMany>,
+
+    /// The creator(s) of the item or collection.
+    pub(crate) creator: Option<OneOrMany>,
+
+    /// The subject(s) of the item or collection.
+    pub(crate) subject: Option<OneOrMany>,
+
+    /// The date of the item, e.g. the broadcast date (`YYYY-MM-DD`, or a less precise variant).
+    pub(crate) date: Option<String>,
+
+    /// The time the item was made public (`YYYY-MM-DD HH:MM:SS`).
+    pub(crate) publicdate: Option<String>,
+
+    /// The time the item was added (`YYYY-MM-DD HH:MM:SS`).
+    pub(crate) addeddate: Option<String>,
+}
+
+/// An Internet Archive metadata value that can either be a single value or multiple values.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub(crate) enum OneOrMany {
+    /// A single value.
+    One(String),
+
+    /// Multiple values.
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    /// Returns all values.
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => Vec::from([value]),
+            OneOrMany::Many(values) => values,
+        }
+    }
+
+    /// Returns all values joined using the separator.
+    fn join(self, separator: &str) -> String {
+        self.into_vec().join(separator)
+    }
+}
+
+/// An Internet Archive file of an item.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct File {
+    /// The name (path) of the file within the item.
+    pub(crate) name: String,
+
+    /// The format of the file.
+    pub(crate) format: Option<String>,
+
+    /// The size of the file (in bytes, as a string).
+    pub(crate) size: Option<String>,
+
+    /// The length of the media content of the file (in seconds or as `[HH:]MM:SS`).
+    pub(crate) length: Option<String>,
+
+    /// The time the file was last modified (a Unix timestamp, as a string).
+    #[serde(default)]
+    pub(crate) mtime: Option<String>,
+}
+
+impl File {
+    /// Returns the length of the media content of the file (in seconds, if known).
+    fn duration(&self) -> Option<u32> {
+        self.length.as_deref().and_then(chapters::parse_timestamp)
+    }
+}
+
+impl TryFrom<Metadata> for Channel {
+    type Error = Error;
+
+    fn try_from(collection: Metadata) -> Result<Self> {
+        let fields = collection.metadata.ok_or(Error::NoRedirectUrlFound)?;
+        let name = fields
+            .title
+            .map(|title| title.join(", "))
+            .unwrap_or_else(|| fields.identifier.clone());
+        let mut link = Url::parse(BASE_URL).expect("URL can always be parsed");
+        link.path_segments_mut()
+            .expect("URL can be a base")
+            .extend(["details", &fields.identifier]);
+        let mut image = Url::parse(BASE_URL).expect("URL can always be parsed");
+        image
+            .path_segments_mut()
+            .expect("URL can be a base")
+            .extend(["services", "img", &fields.identifier]);
+        let categories =
+            channel_categories(fields.subject.map(OneOrMany::into_vec).unwrap_or_default());
+        // The items are fetched (and cached) separately.
+        let items = Vec::new();
+
+        Ok(Channel {
+            title: format!("{name} (via Internet Archive)"),
+            link,
+            description: fields
+                .description
+                .map(|descr| descr.join("\n\n"))
+                .unwrap_or_default(),
+            author: fields.creator.map(|creator| creator.join(", ")),
+            categories,
+            image: Some(image),
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        })
+    }
+}
+
+impl TryFrom<Metadata> for Item {
+    type Error = Error;
+
+    fn try_from(metadata: Metadata) -> Result<Self> {
+        let fields = metadata.metadata.ok_or(Error::NoRedirectUrlFound)?;
+        let file = PREFERRED_FORMATS
+            .iter()
+            .find_map(|format| {
+                metadata
+                    .files
+                    .iter()
+                    .filter(|file| file.format.as_deref() == Some(format))
+                    .min_by(|file1, file2| file1.name.cmp(&file2.name))
+            })
+            .ok_or(Error::NoRedirectUrlFound)?;
+
+        let mut link = Url::parse(BASE_URL).expect("URL can always be parsed");
+        link.path_segments_mut()
+            .expect("URL can be a base")
+            .extend(["details", &fields.identifier]);
+        let mut image = Url::parse(BASE_URL).expect("URL can always be parsed");
+        image
+            .path_segments_mut()
+            .expect("URL can be a base")
+            .extend(["services", "img", &fields.identifier]);
+        let mime_type = Path::new(&file.name)
+            .extension()
+            .and_then(|ext| mime_db::lookup(ext.to_string_lossy()))
+            .unwrap_or("application/octet-stream");
+        let enclosure = Enclosure {
+            file: PathBuf::from(&fields.identifier).join(&file.name),
+            mime_type: String::from(mime_type),
+            length: file
+                .size
+                .as_deref()
+                .and_then(|size| size.parse().ok())
+                .unwrap_or_default(),
+        };
+        let description = fields
+            .description
+            .map(|descr| {
+                format!(
+                    "{}\n\nTaken from Internet Archive: {link}",
+                    descr.join("\n\n")
+                )
+            })
+            .unwrap_or_else(|| format!("Taken from Internet Archive: {link}"));
+        let keywords = fields.subject.map(OneOrMany::into_vec).unwrap_or_default();
+        // Fall back to a fixed timestamp for undated items, so that the feed stays stable.
+        let updated_at = fields
+            .publicdate
+            .as_deref()
+            .or(fields.addeddate.as_deref())
+            .and_then(parse_timestamp)
+            .or_else(|| {
+                file.mtime
+                    .as_deref()
+                    .and_then(|mtime| mtime.parse().ok())
+                    .and_then(|mtime| DateTime::from_timestamp(mtime, 0))
+            })
+            .unwrap_or(DateTime::UNIX_EPOCH);
+        // The date of the item (e.g. the broadcast date) is preferred, if it is precise enough.
+        let published_at = fields
+            .date
+            .as_deref()
+            .and_then(parse_date)
+            .unwrap_or(updated_at);
+
+        Ok(Item {
+            title: fields
+                .title
+                .map(|title| title.join(", "))
+                .unwrap_or_else(|| fields.identifier.clone()),
+            link,
+            description: Some(description),
+            categories: Default::default(),
+            duration: file.duration(),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            enclosure,
+            guid: fields.identifier,
+            keywords,
+            season: None,
+            episode: None,
+            image: Some(image),
+            published_at,
+            updated_at,
+        })
+    }
+}
+
+/// Returns the channel for the uploader.
+///
+/// There is no metadata available for uploaders, so it is based on the uploader only. The
+/// categories are derived from the subjects of the items once these have been retrieved.
+fn uploader_channel(uploader: &str) -> Channel {
+    let mut link = Url::parse(BASE_URL).expect("URL can always be parsed");
+    link.set_path("search");
+    link.query_pairs_mut()
+        .append_pair("query", &format!("uploader:({uploader})"));
+
+    Channel {
+        title: format!("{uploader} (via Internet Archive)"),
+        link,
+        description: String::new(),
+        author: Some(uploader.to_owned()),
+        categories: Vec::new(),
+        image: None,
+        images: BTreeMap::new(),
+        medium: Medium::Podcast,
+        items: Vec::new(),
+    }
+}
+
+/// Returns whether the provided identifier of an item or collection is valid.
+fn is_valid_identifier(identifier: &str) -> bool {
+    !identifier.is_empty()
+        && identifier
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// Returns whether the provided uploader (e-mail address) is valid.
+fn is_valid_uploader(uploader: &str) -> bool {
+    uploader
+        .split_once('@')
+        .is_some_and(|(user, domain)| !user.is_empty() && is_valid_identifier(domain))
+        && !uploader.contains(['(', ')', ' ', '"'])
+}
+
+/// Returns the metadata API URL for the item or collection.
+fn metadata_url(base_url: &Url, identifier: &str) -> Url {
+    let mut url = base_url.clone();
+    url.path_segments_mut()
+        .expect("URL can be a base")
+        .extend(["metadata", identifier]);
+
+    url
+}
+
+/// Parses a timestamp (`YYYY-MM-DD HH:MM:SS`) in the Internet Archive metadata.
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|timestamp| timestamp.and_utc())
+}
+
+/// Parses a date (`YYYY-MM-DD`) in the Internet Archive metadata.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .map(|timestamp| timestamp.and_utc())
+}
+
+/// Fetches a response from the Internet Archive API URL.
+async fn fetch<T: DeserializeOwned>(client: reqwest::Client, url: Url) -> Result<T> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    let result = response.json().await?;
+
+    Ok(result)
+}
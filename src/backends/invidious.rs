@@ -0,0 +1,513 @@
+//! The Invidious back-end.
+//!
+//! It uses the API of a configured Invidious instance to retrieve the YouTube feed (channel or
+//! playlist) and items (videos). The channels and items are the same as the ones of the YouTube
+//! back-end, so that it can be used as an alternative data source for YouTube.
+//! See also: <https://docs.invidious.io/api/>
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use rocket::futures::{stream, StreamExt};
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+
+use super::youtube::{self, CHANNEL_BASE_URL, PLAYLIST_BASE_URL, VIDEO_BASE_URL};
+use super::{
+    channel_categories, mime_extension, BackendConfig, BackendInfo, Channel, Enclosure, Item,
+    Medium,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::chapters::parse_chapters;
+use crate::{Error, Result};
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The maximum number of concurrent requests for the details of videos.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// The information of the Invidious back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "invidious",
+    name: "Invidious",
+    description: "YouTube channel or playlist ID; requires a configured Invidious instance",
+    url_patterns: &[],
+};
+
+/// Creates an Invidious back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        base_url: settings.base_url("instance"),
+        cache: cache.scoped("invidious", settings.ttl),
+    }
+}
+
+/// The Invidious back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The base URL of the configured Invidious instance (if any).
+    base_url: Option<Url>,
+
+    /// The cache store used for channels, playlists, videos and redirect URLs.
+    cache: Cache,
+}
+
+impl Backend {
+    /// Returns the base URL of the configured Invidious instance.
+    fn base_url(&self) -> Result<&Url> {
+        self.base_url
+            .as_ref()
+            .ok_or_else(|| Error::MissingBaseUrl(String::from("invidious")))
+    }
+
+    /// Retrieves the video with the provided ID from the API.
+    async fn video(&self, video_id: &str) -> Result<Video> {
+        let video_url = self
+            .base_url()?
+            .join(&format!("api/v1/videos/{video_id}"))?;
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::Items,
+                video_url.as_str(),
+                fetch(self.client.clone(), video_url.clone()),
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "Invidious"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For Invidious a channel ID is a YouTube channel or playlist ID, like for YouTube.
+        let base_url = self.base_url()?;
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+
+        let (mut channel, video_ids) = if youtube::is_playlist_id(channel_id) {
+            let mut playlist_url = base_url.join(&format!("api/v1/playlists/{channel_id}"))?;
+            println!("⏬ Retrieving playlist {channel_id} from {playlist_url}...");
+            let mut channel = None;
+            let mut video_ids = Vec::new();
+            let mut page = 1;
+            while video_ids.len() < limit {
+                playlist_url
+                    .query_pairs_mut()
+                    .clear()
+                    .append_pair("page", &format!("{page}"));
+                let playlist: Playlist = self
+                    .cache
+                    .get_or_fetch(
+                        CacheKind::Items,
+                        playlist_url.as_str(),
+                        fetch(self.client.clone(), playlist_url.clone()),
+                    )
+                    .await?;
+                let count = playlist.videos.len();
+                video_ids.extend(playlist.videos.iter().map(|video| video.video_id.clone()));
+                channel.get_or_insert_with(|| Channel::from(WithBaseUrl(playlist, base_url)));
+
+                if count == 0 {
+                    break;
+                }
+                page += 1;
+            }
+
+            (channel.expect("at least one page retrieved"), video_ids)
+        } else {
+            let channel_url = base_url.join(&format!("api/v1/channels/{channel_id}"))?;
+            println!("⏬ Retrieving channel {channel_id} from {channel_url}...");
+            let yt_channel: YouTubeChannel = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Channel,
+                    channel_url.as_str(),
+                    fetch(self.client.clone(), channel_url.clone()),
+                )
+                .await?;
+
+            let mut videos_url = base_url.join(&format!("api/v1/channels/{channel_id}/videos"))?;
+            println!("⏬ Retrieving videos from {videos_url}...");
+            let mut video_ids = Vec::new();
+            while video_ids.len() < limit {
+                let videos_res: VideosResponse = self
+                    .cache
+                    .get_or_fetch(
+                        CacheKind::Items,
+                        videos_url.as_str(),
+                        fetch(self.client.clone(), videos_url.clone()),
+                    )
+                    .await?;
+                let count = videos_res.videos.len();
+                video_ids.extend(videos_res.videos.into_iter().map(|video| video.video_id));
+
+                match videos_res.continuation {
+                    Some(continuation) if count > 0 => {
+                        videos_url
+                            .query_pairs_mut()
+                            .clear()
+                            .append_pair("continuation", &continuation);
+                    }
+                    _ => break,
+                }
+            }
+
+            (Channel::from(WithBaseUrl(yt_channel, base_url)), video_ids)
+        };
+
+        // The audio streams of each video are only available in the details of the video, which
+        // are retrieved concurrently (but in order). Videos of which the details cannot be
+        // retrieved or that have no usable audio stream are skipped.
+        let videos = stream::iter(video_ids.into_iter().take(limit))
+            .map(|video_id| async move {
+                let video = self.video(&video_id).await;
+                if let Err(err) = &video {
+                    eprintln!("⚠️ Retrieving video {video_id} failed: {err}, skipping...");
+                }
+
+                video.ok()
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .filter_map(|video| async move { video })
+            .collect::<Vec<_>>()
+            .await;
+        let mut genres = Vec::new();
+        for video in videos {
+            let video_id = video.video_id.clone();
+            genres.extend(video.genre.clone());
+            match Item::try_from(WithBaseUrl(video, base_url)) {
+                Ok(item) => channel.items.push(item),
+                Err(err) => eprintln!("⚠️ Using video {video_id} failed: {err}, skipping..."),
+            }
+        }
+        channel.categories = channel_categories(genres);
+
+        Ok(channel)
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let id_part = file.with_extension("");
+        let video_id = id_part.to_string_lossy();
+        let video = self.video(&video_id).await?;
+
+        video
+            .preferred_format()
+            .map(|format| format.url.clone())
+            .ok_or(Error::NoRedirectUrlFound)
+    }
+}
+
+/// An Invidious object with the base URL of the instance it was retrieved from.
+pub(crate) struct WithBaseUrl<'a, T>(T, &'a Url);
+
+/// An Invidious YouTube channel (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct YouTubeChannel {
+    /// The name of the channel.
+    pub(crate) author: String,
+
+    /// The ID of the channel.
+    pub(crate) author_id: String,
+
+    /// The thumbnails (avatars) of the channel.
+    #[serde(default)]
+    pub(crate) author_thumbnails: Vec<Thumbnail>,
+
+    /// The description of the channel.
+    #[serde(default)]
+    pub(crate) description: String,
+}
+
+/// An Invidious channel videos response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct VideosResponse {
+    /// The videos on this page.
+    pub(crate) videos: Vec<VideoRef>,
+
+    /// The continuation token to retrieve the next page (if any).
+    pub(crate) continuation: Option<String>,
+}
+
+/// An Invidious YouTube playlist (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Playlist {
+    /// The title of the playlist.
+    pub(crate) title: String,
+
+    /// The ID of the playlist.
+    pub(crate) playlist_id: String,
+
+    /// The name of the author of the playlist.
+    pub(crate) author: Option<String>,
+
+    /// The description of the playlist.
+    #[serde(default)]
+    pub(crate) description: String,
+
+    /// The URL of the thumbnail of the playlist.
+    pub(crate) playlist_thumbnail: Option<String>,
+
+    /// The videos of the playlist on this page.
+    #[serde(default)]
+    pub(crate) videos: Vec<VideoRef>,
+}
+
+/// A reference to an Invidious YouTube video.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct VideoRef {
+    /// The ID of the video.
+    pub(crate) video_id: String,
+}
+
+/// An Invidious YouTube video (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Video {
+    /// The ID of the video.
+    pub(crate) video_id: String,
+
+    /// The title of the video.
+    pub(crate) title: String,
+
+    /// The description of the video.
+    #[serde(default)]
+    pub(crate) description: String,
+
+    /// The time the video was published (as a UNIX timestamp).
+    pub(crate) published: i64,
+
+    /// The keywords of the video.
+    #[serde(default)]
+    pub(crate) keywords: Vec<String>,
+
+    /// The genre (YouTube category) of the video.
+    pub(crate) genre: Option<String>,
+
+    /// The length of the video (in seconds).
+    pub(crate) length_seconds: u32,
+
+    /// The thumbnails of the video.
+    #[serde(default)]
+    pub(crate) video_thumbnails: Vec<Thumbnail>,
+
+    /// The adaptive formats (separate audio and video streams) of the video.
+    #[serde(default)]
+    pub(crate) adaptive_formats: Vec<Format>,
+}
+
+impl Video {
+    /// Returns the preferred format of the video.
+    ///
+    /// Like for the YouTube back-end, this is the well-supported, almost always available MP4
+    /// container format with only an audio stream with the highest bitrate.
+    pub(crate) fn preferred_format(&self) -> Option<&Format> {
+        self.adaptive_formats
+            .iter()
+            .filter(|format| format.mime_type.starts_with("audio/mp4"))
+            .max_by_key(|format| format.bitrate())
+    }
+}
+
+/// An Invidious YouTube video format (stream).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Format {
+    /// The URL of the stream.
+    pub(crate) url: String,
+
+    /// The MIME type of the stream (including codec parameters).
+    #[serde(rename = "type")]
+    pub(crate) mime_type: String,
+
+    /// The bitrate of the stream.
+    pub(crate) bitrate: Option<String>,
+
+    /// The content length of the stream.
+    pub(crate) clen: Option<String>,
+}
+
+impl Format {
+    /// Returns the bitrate of the stream (or 0 if unknown).
+    fn bitrate(&self) -> u64 {
+        self.bitrate
+            .as_deref()
+            .and_then(|bitrate| bitrate.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// An Invidious thumbnail.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Thumbnail {
+    /// The (possibly relative) URL of the thumbnail.
+    pub(crate) url: String,
+
+    /// The width of the thumbnail.
+    #[serde(default)]
+    pub(crate) width: u32,
+
+    /// The height of the thumbnail.
+    #[serde(default)]
+    pub(crate) height: u32,
+}
+
+/// Returns the (absolute) URL of the largest thumbnail (if any).
+fn largest_thumbnail(thumbnails: &[Thumbnail], base_url: &Url) -> Option<Url> {
+    thumbnails
+        .iter()
+        .max_by_key(|tn| tn.width * tn.height)
+        .and_then(|tn| base_url.join(&tn.url).ok())
+}
+
+impl From<WithBaseUrl<'_, YouTubeChannel>> for Channel {
+    fn from(WithBaseUrl(yt_channel, base_url): WithBaseUrl<'_, YouTubeChannel>) -> Self {
+        let mut link = Url::parse(CHANNEL_BASE_URL).expect("valid URL");
+        link.path_segments_mut()
+            .expect("valid URL")
+            .push(&yt_channel.author_id);
+        let image = largest_thumbnail(&yt_channel.author_thumbnails, base_url);
+        let images = yt_channel
+            .author_thumbnails
+            .iter()
+            .filter_map(|tn| Some((tn.width, base_url.join(&tn.url).ok()?)))
+            .collect();
+        // The items are added separately and the categories are derived from them.
+        let categories = Vec::new();
+        let items = Vec::new();
+
+        Channel {
+            title: format!("{0} (via YouTube)", yt_channel.author),
+            link,
+            description: yt_channel.description,
+            author: Some(yt_channel.author),
+            categories,
+            image,
+            images,
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl From<WithBaseUrl<'_, Playlist>> for Channel {
+    fn from(WithBaseUrl(playlist, base_url): WithBaseUrl<'_, Playlist>) -> Self {
+        let mut link = Url::parse(PLAYLIST_BASE_URL).expect("valid URL");
+        link.query_pairs_mut()
+            .append_pair("list", &playlist.playlist_id);
+        let image = playlist
+            .playlist_thumbnail
+            .and_then(|thumbnail| base_url.join(&thumbnail).ok());
+        // The items are added separately and the categories are derived from them.
+        let categories = Vec::new();
+        let items = Vec::new();
+
+        Channel {
+            title: format!("{0} (via YouTube)", playlist.title),
+            link,
+            description: playlist.description,
+            author: playlist.author,
+            categories,
+            image,
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl TryFrom<WithBaseUrl<'_, Video>> for Item {
+    type Error = Error;
+
+    fn try_from(WithBaseUrl(video, base_url): WithBaseUrl<'_, Video>) -> Result<Self> {
+        let format = video.preferred_format().ok_or(Error::NoRedirectUrlFound)?;
+
+        // Strip parameters from MIME type; some clients are scared of them and they are no
+        // necessary.
+        let mut mime_type = format.mime_type.clone();
+        if let Some(sep_idx) = mime_type.find(';') {
+            mime_type.truncate(sep_idx);
+        }
+        let extension = mime_extension(&mime_type).unwrap_or_default();
+        let file = PathBuf::from(&video.video_id).with_extension(extension);
+        let length = format
+            .clen
+            .as_deref()
+            .and_then(|clen| clen.parse().ok())
+            .unwrap_or_default();
+        let enclosure = Enclosure {
+            file,
+            mime_type,
+            length,
+        };
+
+        let mut link = Url::parse(VIDEO_BASE_URL).expect("valid URL");
+        link.query_pairs_mut().append_pair("v", &video.video_id);
+        let description = Some(format!(
+            "{}\n\nTaken from YouTube: {link}",
+            video.description
+        ));
+        let categories = video
+            .description
+            .split_whitespace()
+            .filter(|word| word.len() > 1 && word.starts_with('#'))
+            .map(|hashtag| {
+                let url = Url::parse(&format!(
+                    "https://www.youtube.com/hashtag/{}",
+                    hashtag.trim_start_matches('#')
+                ))
+                .expect("valid URL");
+
+                (hashtag.to_string(), url)
+            })
+            .collect();
+        let chapters = parse_chapters(&video.description);
+        let image = largest_thumbnail(&video.video_thumbnails, base_url);
+        // Like for the YouTube back-end, only use the date of publication at noon.
+        let published_at = DateTime::<Utc>::from_timestamp(video.published, 0)
+            .and_then(|published_at| published_at.date_naive().and_hms_opt(12, 0, 0))
+            .map(|timestamp| timestamp.and_utc())
+            .unwrap_or_default();
+        // There is no updated at timestamp available, really.
+        let updated_at = published_at;
+
+        Ok(Item {
+            title: video.title,
+            link,
+            description,
+            categories,
+            enclosure,
+            duration: Some(video.length_seconds),
+            chapters,
+            transcripts: Vec::new(),
+            guid: video.video_id,
+            keywords: video.keywords,
+            season: None,
+            episode: None,
+            image,
+            published_at,
+            updated_at,
+        })
+    }
+}
+
+/// Fetches a response from the Invidious API URL.
+async fn fetch<T: DeserializeOwned>(client: reqwest::Client, url: Url) -> Result<T> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    let result = response.json().await?;
+
+    Ok(result)
+}
@@ -0,0 +1,362 @@
+//! The local directory back-end.
+//!
+//! It publishes the audio and video files in the configured (named) directories on disk. The
+//! tags of the files are used for the metadata of the items, and the files are served directly
+//! instead of redirecting to them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lofty::prelude::{Accessor, AudioFile, TaggedFileExt};
+use lofty::tag::Tag;
+use reqwest::Url;
+use rocket::http::uri::Absolute;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::uri;
+
+use super::{channel_categories, BackendInfo, Channel, Enclosure, Item, LocalContent, Medium};
+use crate::transcode::MediaFormat;
+use crate::{Config, Error, Result};
+
+/// The extension of the virtual download file paths of the cover art embedded in files.
+const COVER_EXTENSION: &str = "cover";
+
+/// The names of the image files in a directory that are used as cover art, in order of
+/// preference.
+const COVER_FILE_NAMES: [&str; 4] = ["cover.jpg", "cover.png", "folder.jpg", "folder.png"];
+
+/// The extensions of image files next to a file that are used as its cover art.
+const COVER_FILE_EXTENSIONS: [&str; 2] = ["jpg", "png"];
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The local directory back-end configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct LocalConfig {
+    /// The directories that are published (by name).
+    directories: HashMap<String, PathBuf>,
+}
+
+/// The information of the Local back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "local",
+    name: "Local",
+    description: "name of a configured directory",
+    url_patterns: &[],
+};
+
+/// Creates a local directory back-end.
+pub(crate) fn backend(config: &Config) -> Backend {
+    Backend {
+        directories: config.local.directories.clone(),
+        public_url: config.public_url.clone(),
+    }
+}
+
+/// The local directory back-end.
+pub struct Backend {
+    /// The directories that are published (by name).
+    directories: HashMap<String, PathBuf>,
+
+    /// The public URL at which the application is hosted or proxied from.
+    public_url: String,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "Local"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For the local directory back-end a channel ID is the name of a configured directory.
+        let directory = self
+            .directories
+            .get(channel_id)
+            .cloned()
+            .ok_or_else(|| Error::UnsupportedChannel(channel_id.to_string()))?;
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+        let name = channel_id.to_owned();
+        let public_url = self.public_url.clone();
+
+        println!(
+            "⏬ Reading directory {name} from {}...",
+            directory.display()
+        );
+        tokio::task::spawn_blocking(move || read_channel(&public_url, &name, &directory, limit))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    async fn redirect_url(&self, _file: &Path) -> Result<String> {
+        // The files are always served directly.
+        Err(Error::NoRedirectUrlFound)
+    }
+
+    async fn local_content(&self, file: &Path) -> Result<Option<LocalContent>> {
+        let mut components = file.components();
+        let Some(directory) = components
+            .next()
+            .and_then(|name| self.directories.get(&*name.as_os_str().to_string_lossy()))
+        else {
+            return Ok(None);
+        };
+        let relative_path = components.as_path();
+        if relative_path
+            .iter()
+            .any(|component| component.to_string_lossy().starts_with('.'))
+        {
+            return Ok(None);
+        }
+        let path = directory.join(relative_path);
+
+        // Only serve files within the directory.
+        let is_cover = path.extension().is_some_and(|ext| ext == COVER_EXTENSION);
+        let media_path = if is_cover {
+            path.with_extension("")
+        } else {
+            path
+        };
+        let directory = directory.canonicalize()?;
+        let media_path = match media_path.canonicalize() {
+            Ok(media_path) if media_path.starts_with(&directory) => media_path,
+            _ => return Ok(None),
+        };
+        if !is_cover {
+            // Only serve the files that are published, i.e. media files and their cover art.
+            let is_published =
+                media_mime_type(&media_path).is_some() || is_cover_file(&directory, &media_path);
+
+            return Ok(is_published.then_some(LocalContent::File(media_path)));
+        }
+        if media_mime_type(&media_path).is_none() {
+            return Ok(None);
+        }
+
+        tokio::task::spawn_blocking(move || {
+            let tagged_file = lofty::read_from_path(&media_path).ok()?;
+            let picture = tagged_file
+                .primary_tag()
+                .or_else(|| tagged_file.first_tag())
+                .and_then(|tag| tag.pictures().first())?;
+            let mime_type = picture
+                .mime_type()
+                .map(|mime_type| mime_type.as_str().to_owned())
+                .unwrap_or_else(|| String::from("image/jpeg"));
+
+            Some(LocalContent::Data {
+                mime_type,
+                data: picture.data().to_vec(),
+            })
+        })
+        .await
+        .map_err(|err| Error::Io(std::io::Error::other(err)))
+    }
+}
+
+/// Returns the download URL for the provided download file path.
+fn download_url(public_url: &str, file: PathBuf) -> Url {
+    let url = uri!(
+        Absolute::parse(public_url).expect("valid URL"),
+        crate::get_download(
+            backend_id = INFO.id,
+            file = file,
+            format = None::<MediaFormat>
+        )
+    );
+
+    Url::parse(&url.to_string()).expect("valid URL")
+}
+
+/// Reads the channel with (at most the limit of) the most recent files from the directory.
+fn read_channel(public_url: &str, name: &str, directory: &Path, limit: usize) -> Result<Channel> {
+    let mut files = Vec::new();
+    find_media_files(directory, &mut files)?;
+    files.sort_by_key(|(_, metadata)| std::cmp::Reverse(metadata.modified().ok()));
+    files.truncate(limit);
+
+    let image = COVER_FILE_NAMES
+        .iter()
+        .find(|file_name| directory.join(file_name).is_file())
+        .map(|file_name| download_url(public_url, PathBuf::from(name).join(file_name)));
+    let (items, genres): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .filter_map(|(path, metadata)| {
+            let relative_path = path.strip_prefix(directory).ok()?;
+            let file = PathBuf::from(name).join(relative_path);
+
+            Some(read_item(public_url, &path, file, &metadata, image.clone()))
+        })
+        .unzip();
+
+    Ok(Channel {
+        title: String::from(name),
+        link: Url::parse(public_url)?,
+        description: format!("The files in {name}"),
+        author: None,
+        categories: channel_categories(genres.into_iter().flatten()),
+        image,
+        images: BTreeMap::new(),
+        medium: Medium::Music,
+        items,
+    })
+}
+
+/// Reads the item for the file at the provided path using its tags, along with its genre.
+///
+/// If the file has no cover art of its own, the provided channel image is used.
+fn read_item(
+    public_url: &str,
+    path: &Path,
+    file: PathBuf,
+    metadata: &Metadata,
+    channel_image: Option<Url>,
+) -> (Item, Option<String>) {
+    let tagged_file = lofty::read_from_path(path).ok();
+    let tag = tagged_file.as_ref().and_then(|tagged_file| {
+        tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag())
+    });
+    let tag_value = |get: fn(&Tag) -> Option<std::borrow::Cow<'_, str>>| {
+        tag.and_then(get).map(|value| value.into_owned())
+    };
+    let title = tag_value(Tag::title).unwrap_or_else(|| {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    });
+    let artist = tag_value(Tag::artist);
+    let album = tag_value(Tag::album);
+    let genre = tag_value(Tag::genre);
+    let description = tag_value(Tag::comment).or_else(|| match (&album, &artist) {
+        (Some(album), Some(artist)) => Some(format!("From {album} by {artist}")),
+        (Some(album), None) => Some(format!("From {album}")),
+        (None, _) => None,
+    });
+    let duration = tagged_file
+        .as_ref()
+        .map(|tagged_file| tagged_file.properties().duration().as_secs() as u32);
+
+    let image = if tag.is_some_and(|tag| !tag.pictures().is_empty()) {
+        let mut cover_file = file.clone().into_os_string();
+        cover_file.push(format!(".{COVER_EXTENSION}"));
+
+        Some(download_url(public_url, PathBuf::from(cover_file)))
+    } else {
+        COVER_FILE_EXTENSIONS
+            .iter()
+            .find(|ext| path.with_extension(ext).is_file())
+            .map(|ext| download_url(public_url, file.with_extension(ext)))
+            .or(channel_image)
+    };
+    let mime_type = media_mime_type(path).unwrap_or("application/octet-stream");
+    // Fall back to a fixed timestamp if the platform does not provide one, so that the feed stays
+    // stable.
+    let modified_at = metadata
+        .modified()
+        .or_else(|_| metadata.created())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or(DateTime::UNIX_EPOCH);
+    let guid = file.to_string_lossy().into_owned();
+    let enclosure = Enclosure {
+        file: file.clone(),
+        mime_type: String::from(mime_type),
+        length: metadata.len(),
+    };
+    let keywords = [artist, album, genre.clone()]
+        .into_iter()
+        .flatten()
+        .collect();
+    let item = Item {
+        title,
+        link: download_url(public_url, file),
+        description,
+        categories: Default::default(),
+        enclosure,
+        duration,
+        chapters: Vec::new(),
+        transcripts: Vec::new(),
+        guid,
+        keywords,
+        season: tag.and_then(Tag::disk),
+        episode: tag.and_then(Tag::track),
+        image,
+        published_at: modified_at,
+        updated_at: modified_at,
+    };
+
+    (item, genre)
+}
+
+/// Returns the MIME type of the file at the path if it is an audio or video file.
+fn media_mime_type(path: &Path) -> Option<&'static str> {
+    path.extension()
+        .and_then(|ext| mime_db::lookup(ext.to_string_lossy()))
+        .filter(|mime_type| mime_type.starts_with("audio/") || mime_type.starts_with("video/"))
+}
+
+/// Returns whether the file at the path is used as cover art (of the directory or a file).
+///
+/// This is the case for image files with one of the cover art file names in the directory
+/// itself and image files with one of the cover art extensions next to a media file with the
+/// same name.
+fn is_cover_file(directory: &Path, path: &Path) -> bool {
+    let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+        return false;
+    };
+    if path.parent() == Some(directory) && COVER_FILE_NAMES.contains(&file_name) {
+        return true;
+    }
+    let is_image = path.extension().is_some_and(|ext| {
+        COVER_FILE_EXTENSIONS
+            .iter()
+            .any(|cover_ext| ext == *cover_ext)
+    });
+    let Some(parent) = path.parent().filter(|_| is_image) else {
+        return false;
+    };
+
+    fs::read_dir(parent).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let sibling = entry.path();
+
+            sibling.file_stem() == path.file_stem() && media_mime_type(&sibling).is_some()
+        })
+    })
+}
+
+/// Finds the audio and video files in the directory (recursively).
+///
+/// Hidden files and directories are skipped. Symbolic links to files are followed, but symbolic
+/// links to directories are not, so that links that (indirectly) point to themselves do not lead
+/// to endless recursion.
+fn find_media_files(directory: &Path, files: &mut Vec<(PathBuf, Metadata)>) -> Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        // The file type of a directory entry is that of the link itself, if it is a link.
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_media_files(&path, files)?;
+        } else if media_mime_type(&path).is_some() {
+            // Skip dangling links and links to directories.
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => files.push((path, metadata)),
+                _ => continue,
+            }
+        }
+    }
+
+    Ok(())
+}
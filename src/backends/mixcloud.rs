@@ -3,24 +3,33 @@
 //! It uses the Mixcloud API to retrieve the feed (user) and items (cloudcasts)).
 //! See also: <https://www.mixcloud.com/developers/>
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
-use cached::macros::cached;
 use chrono::{DateTime, Utc};
+use reqwest::header::CONTENT_LENGTH;
 use reqwest::Url;
-use rocket::serde::Deserialize;
-use youtube_dl::{YoutubeDl, YoutubeDlOutput};
-
-use super::{Channel, Enclosure, Item};
+use rocket::serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use youtube_dl::YoutubeDlOutput;
+
+use super::{
+    mime_extension, path_segments, BackendConfig, BackendInfo, Channel, Chapter, Enclosure, Item,
+    Medium, YoutubeDlOptions,
+};
+use crate::cache::{Cache, CacheKind};
 use crate::{Error, Result};
 
-/// The base URL for the Mixcloud API.
+/// The default base URL for the Mixcloud API.
 const API_BASE_URL: &str = "https://api.mixcloud.com";
 
-/// The base URL for downloading Mixcloud files.
+/// The default base URL for downloading Mixcloud files.
 const FILES_BASE_URL: &str = "https://www.mixcloud.com";
 
+/// The paths of Mixcloud URLs that do not refer to users.
+const RESERVED_PATHS: [&str; 5] = ["categories", "discover", "search", "tag", "upload"];
+
 /// The default bitrate used by Mixcloud.
 const DEFAULT_BITRATE: u64 = 64 * 1024;
 
@@ -30,13 +39,59 @@ const DEFAULT_FILE_TYPE: &str = "audio/mp4";
 /// The default page size.
 const DEFAULT_PAGE_SIZE: usize = 50;
 
+/// The number of newest cloudcasts of which the redirect URLs are resolved in the background.
+///
+/// Resolving a redirect URL runs yt-dlp, so this is not done for all cloudcasts of a feed.
+const MAX_PREFETCHED_REDIRECT_URLS: usize = 10;
+
+/// The maximum number of redirect URLs that are resolved concurrently in the background.
+const MAX_BACKGROUND_RESOLVES: usize = 2;
+
+/// The permits for resolving redirect URLs in the background.
+static BACKGROUND_RESOLVE_PERMITS: Semaphore = Semaphore::const_new(MAX_BACKGROUND_RESOLVES);
+
+/// The maximum number of cloudcast sections that are fetched concurrently in the background.
+const MAX_BACKGROUND_SECTION_FETCHES: usize = 4;
+
+/// The permits for fetching cloudcast sections in the background.
+static BACKGROUND_SECTION_PERMITS: Semaphore = Semaphore::const_new(MAX_BACKGROUND_SECTION_FETCHES);
+
+/// The information of the Mixcloud back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "mixcloud",
+    name: "Mixcloud",
+    description: "Mixcloud username",
+    url_patterns: &["https://www.mixcloud.com/*"],
+};
+
 /// Creates a Mixcloud back-end.
-pub(crate) fn backend() -> Backend {
-    Backend
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        api_base_url: settings.base_url_or("api", API_BASE_URL),
+        files_base_url: settings.base_url_or("files", FILES_BASE_URL),
+        youtube_dl: settings.youtube_dl_options(),
+        cache: cache.scoped("mixcloud", settings.ttl),
+    }
 }
 
 /// The Mixcloud back-end.
-pub struct Backend;
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The base URL for the Mixcloud API.
+    api_base_url: Url,
+
+    /// The base URL for downloading Mixcloud files.
+    files_base_url: Url,
+
+    /// The options used for running `yt-dlp`.
+    youtube_dl: YoutubeDlOptions,
+
+    /// The cache store used for users, cloudcasts and redirect URLs.
+    cache: Cache,
+}
 
 #[async_trait]
 impl super::Backend for Backend {
@@ -46,23 +101,37 @@ impl super::Backend for Backend {
 
     async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
         // For Mixcloud a channel ID is some user name.
-        let mut user_url = Url::parse(API_BASE_URL).expect("URL can always be parsed");
+        let mut user_url = self.api_base_url.clone();
         user_url.set_path(channel_id);
 
         println!("⏬ Retrieving user {channel_id} from {user_url}...");
-        let user = fetch_user(user_url).await?;
+        let user = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                user_url.as_str(),
+                fetch_user(self.client.clone(), user_url.clone()),
+            )
+            .await?;
 
         // The items of a channel are the user's cloudcasts.
         let mut limit = item_limit.unwrap_or(DEFAULT_PAGE_SIZE);
         let mut offset = 0;
-        let mut cloudcasts_url = Url::parse(API_BASE_URL).expect("URL can always be parsed");
+        let mut cloudcasts_url = self.api_base_url.clone();
         cloudcasts_url.set_path(&format!("{channel_id}/cloudcasts/"));
         println!("⏬ Retrieving cloudcasts of user {channel_id} from {cloudcasts_url}...");
 
         set_paging_query(&mut cloudcasts_url, limit, offset);
         let mut cloudcasts = Vec::with_capacity(50); // The initial limit
         loop {
-            let cloudcasts_res: CloudcastsResponse = fetch_cloudcasts(cloudcasts_url).await?;
+            let cloudcasts_res: CloudcastsResponse = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    cloudcasts_url.as_str(),
+                    fetch_cloudcasts(self.client.clone(), cloudcasts_url.clone()),
+                )
+                .await?;
             let count = cloudcasts_res.items.len();
             cloudcasts.extend(cloudcasts_res.items);
 
@@ -85,21 +154,92 @@ impl super::Backend for Backend {
             }
         }
 
-        Ok(Channel::from(UserWithCloudcasts(user, cloudcasts)))
+        let mut channel = Channel::from(UserWithCloudcasts(user, cloudcasts));
+
+        // Use the actual content lengths for the enclosures if their redirect URLs have been
+        // resolved already; resolve them in the background otherwise (for the newest cloudcasts
+        // only) so they can be used later. The same goes for the sections of the cloudcasts that
+        // are used as chapters.
+        for (index, item) in channel.items.iter_mut().enumerate() {
+            let key = download_key(&item.enclosure.file);
+            let sections_url = self.api_base_url.join(&key)?;
+            match self.cache.peek(CacheKind::Items, sections_url.as_str())? {
+                Some(CloudcastSections { sections }) => item.chapters = section_chapters(sections),
+                None => self.cache.prefetch(
+                    CacheKind::Items,
+                    sections_url.as_str(),
+                    fetch_sections_in_background(self.client.clone(), sections_url.clone()),
+                )?,
+            }
+            match self.cache.peek(CacheKind::RedirectUrl, &key)? {
+                Some(ResolvedUrl {
+                    content_length: Some(length),
+                    ..
+                }) => item.enclosure.length = length,
+                Some(_) => {}
+                None if index >= MAX_PREFETCHED_REDIRECT_URLS => {}
+                None => self.cache.prefetch(
+                    CacheKind::RedirectUrl,
+                    &key,
+                    retrieve_redirect_url_in_background(
+                        self.client.clone(),
+                        self.youtube_dl.clone(),
+                        self.files_base_url.clone(),
+                        key.clone(),
+                    ),
+                )?,
+            }
+        }
+
+        Ok(channel)
     }
 
     async fn redirect_url(&self, file: &Path) -> Result<String> {
-        let key = format!("/{}/", file.with_extension("").to_string_lossy());
+        let key = download_key(file);
+        let resolved_url = self
+            .cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                &key,
+                retrieve_redirect_url(
+                    self.client.clone(),
+                    self.youtube_dl.clone(),
+                    self.files_base_url.clone(),
+                    key.clone(),
+                ),
+            )
+            .await?;
+
+        Ok(resolved_url.url)
+    }
 
-        retrieve_redirect_url(&key).await
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // The first path segment of a user URL is the user name.
+        let user = match path_segments(url)[..] {
+            [user, ..] if !RESERVED_PATHS.contains(&user) => Some(user),
+            _ => None,
+        };
+
+        Ok(user.map(String::from))
     }
 }
 
+/// A redirect URL resolved for a Mixcloud cloudcast.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct ResolvedUrl {
+    /// The resolved redirect URL.
+    url: String,
+
+    /// The content length of the media content at the redirect URL (if known).
+    content_length: Option<u64>,
+}
+
 /// A Mixcloud user with its cloudcasts.
 pub(crate) struct UserWithCloudcasts(User, Vec<Cloudcast>);
 
 /// A Mixcloud user (response).
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct User {
     /// The name of the user.
@@ -116,7 +256,7 @@ pub(crate) struct User {
 }
 
 /// A collection of different sizes/variants of a picture.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct Pictures {
     /// The URL of a large picture of the user.
@@ -124,7 +264,7 @@ pub(crate) struct Pictures {
 }
 
 /// The Mixcloud cloudcasts response.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct CloudcastsResponse {
     /// The contained cloudcast items.
@@ -136,7 +276,7 @@ pub(crate) struct CloudcastsResponse {
 }
 
 /// The Mixcloud paging info.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct CloudcastsPaging {
     /// The API URL of the next page.
@@ -144,7 +284,7 @@ pub(crate) struct CloudcastsPaging {
 }
 
 /// A Mixcloud cloudcast.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct Cloudcast {
     /// The key of the cloudcast.
@@ -175,8 +315,50 @@ pub(crate) struct Cloudcast {
     pub(crate) audio_length: u32,
 }
 
+/// A Mixcloud cloudcast with its sections (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct CloudcastSections {
+    /// The sections (played tracks or chapters) of the cloudcast.
+    #[serde(default)]
+    pub(crate) sections: Vec<Section>,
+}
+
+/// A Mixcloud cloudcast section.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Section {
+    /// The start time of the section (in seconds, if known).
+    pub(crate) start_time: Option<u32>,
+
+    /// The track played in the section (if it is a track section).
+    pub(crate) track: Option<Track>,
+
+    /// The title of the chapter (if it is a chapter section).
+    pub(crate) chapter: Option<String>,
+}
+
+/// A Mixcloud track.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Track {
+    /// The name of the track.
+    pub(crate) name: String,
+
+    /// The artist of the track.
+    pub(crate) artist: Artist,
+}
+
+/// A Mixcloud artist.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Artist {
+    /// The name of the artist.
+    pub(crate) name: String,
+}
+
 /// A Mixcloud cloudcast tag.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct Tag {
     /// The name of the tag.
@@ -199,6 +381,8 @@ impl From<UserWithCloudcasts> for Channel {
             author: Some(user.name),
             categories,
             image: Some(user.pictures.large),
+            images: BTreeMap::new(),
+            medium: Medium::Music,
             items,
         }
     }
@@ -207,7 +391,7 @@ impl From<UserWithCloudcasts> for Channel {
 impl From<Cloudcast> for Item {
     fn from(cloudcast: Cloudcast) -> Self {
         let mut file = PathBuf::from(cloudcast.key.trim_end_matches('/'));
-        let extension = mime_db::extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
+        let extension = mime_extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
         file.set_extension(extension);
 
         // FIXME: Don't hardcode the description!
@@ -232,8 +416,12 @@ impl From<Cloudcast> for Item {
             categories,
             enclosure,
             duration: Some(cloudcast.audio_length),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
             guid: cloudcast.slug,
             keywords,
+            season: None,
+            episode: None,
             image: Some(cloudcast.pictures.large),
             published_at: cloudcast.created_time,
             updated_at: cloudcast.updated_time,
@@ -241,37 +429,79 @@ impl From<Cloudcast> for Item {
     }
 }
 
+/// Returns the chapters for the provided cloudcast sections.
+///
+/// Sections without a start time are skipped.
+fn section_chapters(sections: Vec<Section>) -> Vec<Chapter> {
+    let mut chapters = sections
+        .into_iter()
+        .filter_map(|section| {
+            let title = match (section.track, section.chapter) {
+                (Some(track), _) => format!("{} - {}", track.artist.name, track.name),
+                (None, Some(chapter)) => chapter,
+                (None, None) => return None,
+            };
+
+            Some(Chapter {
+                start_time: section.start_time?,
+                title,
+            })
+        })
+        .collect::<Vec<_>>();
+    chapters.sort_by_key(|chapter| chapter.start_time);
+
+    chapters
+}
+
+/// Returns the download key (cloudcast key) for the provided download file path.
+fn download_key(file: &Path) -> String {
+    format!("/{}/", file.with_extension("").to_string_lossy())
+}
+
 /// Returns the estimated file size in bytes for a given duration.
 ///
-/// This uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
+/// This is only used as long as the actual file size is not known.
+/// It uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
 fn estimated_file_size(duration: u32) -> u64 {
     DEFAULT_BITRATE * duration as u64 / 8
 }
 
 /// Fetches the user from the URL.
-///
-/// If the result is [`Ok`], the user will be cached for 24 hours for the given URL.
-#[cached(key = "String", convert = r#"{ url.to_string() }"#, ttl = 86400)]
-///
-/// If the result is [`Ok`], the user will be cached for 24 hours for the given username.
-async fn fetch_user(url: Url) -> Result<User> {
-    let response = reqwest::get(url).await?.error_for_status()?;
+async fn fetch_user(client: reqwest::Client, url: Url) -> Result<User> {
+    let response = client.get(url).send().await?.error_for_status()?;
     let user = response.json().await?;
 
     Ok(user)
 }
 
 /// Fetches cloudcasts from the URL.
-///
-/// If the result is [`Ok`], the cloudcasts will be cached for 24 hours for the given URL.
-#[cached(key = "String", convert = r#"{ url.to_string() }"#, ttl = 86400)]
-async fn fetch_cloudcasts(url: Url) -> Result<CloudcastsResponse> {
-    let response = reqwest::get(url).await?.error_for_status()?;
+async fn fetch_cloudcasts(client: reqwest::Client, url: Url) -> Result<CloudcastsResponse> {
+    let response = client.get(url).send().await?.error_for_status()?;
     let cloudcasts_res = response.json().await?;
 
     Ok(cloudcasts_res)
 }
 
+/// Fetches the sections of a cloudcast from the URL in the background.
+///
+/// The number of cloudcast sections that are fetched concurrently in the background is limited
+/// (see [`MAX_BACKGROUND_SECTION_FETCHES`]).
+async fn fetch_sections_in_background(
+    client: reqwest::Client,
+    url: Url,
+) -> Result<CloudcastSections> {
+    let _permit = BACKGROUND_SECTION_PERMITS
+        .acquire()
+        .await
+        .expect("semaphore is never closed");
+
+    println!("⏬ Retrieving sections of cloudcast from {url}...");
+    let response = client.get(url).send().await?.error_for_status()?;
+    let cloudcast_sections = response.json().await?;
+
+    Ok(cloudcast_sections)
+}
+
 /// Set paging query pairs for URL.
 ///
 /// The limit is capped to the default page size. Another request will be necessary to retrieve
@@ -286,27 +516,71 @@ fn set_paging_query(url: &mut Url, limit: usize, offset: usize) {
         .append_pair("offset", &format!("{offset}"));
 }
 
+/// Retrieves the redirect URL for the provided Mixcloud cloudcast key in the background.
+///
+/// The number of redirect URLs that are retrieved concurrently in the background is limited (see
+/// [`MAX_BACKGROUND_RESOLVES`]).
+async fn retrieve_redirect_url_in_background(
+    client: reqwest::Client,
+    youtube_dl: YoutubeDlOptions,
+    files_base_url: Url,
+    download_key: String,
+) -> Result<ResolvedUrl> {
+    let _permit = BACKGROUND_RESOLVE_PERMITS
+        .acquire()
+        .await
+        .expect("semaphore is never closed");
+
+    retrieve_redirect_url(client, youtube_dl, files_base_url, download_key).await
+}
+
 /// Retrieves the redirect URL for the provided Mixcloud cloudcast key.
 ///
-/// If the result is [`Ok`], the redirect URL will be cached for 24 hours for the given cloudcast
-/// key.
-#[cached(
-    key = "String",
-    convert = r#"{ download_key.to_owned() }"#,
-    ttl = 86400
-)]
-async fn retrieve_redirect_url(download_key: &str) -> Result<String> {
-    let mut url = Url::parse(FILES_BASE_URL).expect("URL can always be parsed");
-    url.set_path(download_key);
+/// The content length of the media content at the redirect URL is determined as well, if
+/// possible.
+async fn retrieve_redirect_url(
+    client: reqwest::Client,
+    youtube_dl: YoutubeDlOptions,
+    files_base_url: Url,
+    download_key: String,
+) -> Result<ResolvedUrl> {
+    let mut url = files_base_url;
+    url.set_path(&download_key);
 
     println!("🌍 Determining direct URL for {download_key}...");
     // Select the well-supported, almost always available MP4 container format that is directly
     // available (so no HLS or DASH). This unfortunately does reduce the bitrate to 64 kbps.
-    let output = YoutubeDl::new(url).format("http").run_async().await?;
+    let output = youtube_dl.command(url).format("http").run_async().await?;
+
+    let url = match output {
+        YoutubeDlOutput::SingleVideo(yt_item) => yt_item.url.ok_or(Error::NoRedirectUrlFound)?,
+        _ => return Err(Error::NoRedirectUrlFound),
+    };
+    let content_length = fetch_content_length(client, &url).await;
+
+    Ok(ResolvedUrl {
+        url,
+        content_length,
+    })
+}
 
-    if let YoutubeDlOutput::SingleVideo(yt_item) = output {
-        yt_item.url.ok_or(Error::NoRedirectUrlFound)
-    } else {
-        Err(Error::NoRedirectUrlFound)
-    }
+/// Fetches the content length of the media content at the provided URL.
+///
+/// If the content length cannot be determined, [`None`] is returned.
+async fn fetch_content_length(client: reqwest::Client, url: &str) -> Option<u64> {
+    let response = client
+        .head(url)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+
+    response
+        .headers()
+        .get(CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
 }
@@ -0,0 +1,605 @@
+//! The PeerTube back-end.
+//!
+//! It uses the REST API of the PeerTube instance to retrieve the feed (video channel or playlist)
+//! and items (videos).
+//! See also: <https://docs.joinpeertube.org/api-rest-reference.html>
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+
+use super::{
+    channel_categories, is_allowed_url, path_segments, BackendConfig, BackendInfo, Channel,
+    Enclosure, Item, Medium,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::{Error, Result};
+
+/// The prefix of channel IDs that refer to playlists.
+const PLAYLIST_PREFIX: &str = "playlist:";
+
+/// The resolution ID PeerTube uses for audio-only files.
+const AUDIO_ONLY_RESOLUTION: u32 = 0;
+
+/// The default file (MIME) type used by PeerTube.
+const DEFAULT_FILE_TYPE: &str = "video/mp4";
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The maximum page size supported by the API.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// The information of the PeerTube back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "peertube",
+    name: "PeerTube",
+    description: "video channel handle like name@host or playlist ID like playlist:id@host",
+    url_patterns: &[],
+};
+
+/// Creates a PeerTube back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        url_patterns: settings.url_patterns.clone(),
+        cache: cache.scoped("peertube", settings.ttl),
+    }
+}
+
+/// The PeerTube back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The configured URL patterns of the instances that are allowed even if they are not public.
+    url_patterns: Vec<String>,
+
+    /// The cache store used for channels, playlists, videos and redirect URLs.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "PeerTube"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For PeerTube a channel ID is the handle of a video channel (`name@host`) or the ID of a
+        // playlist prefixed with `playlist:` (`playlist:id@host`).
+        let (name, host) = channel_id
+            .rsplit_once('@')
+            .filter(|(name, host)| !name.is_empty() && !name.contains('/') && is_valid_host(host))
+            .ok_or_else(|| Error::UnsupportedChannel(channel_id.to_string()))?;
+        let base_url = self
+            .instance_url(host)
+            .await?
+            .ok_or_else(|| Error::UnsupportedChannel(channel_id.to_string()))?;
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+
+        let (mut channel, videos_path) = match name.strip_prefix(PLAYLIST_PREFIX) {
+            Some(playlist_id) => {
+                let playlist_url =
+                    base_url.join(&format!("api/v1/video-playlists/{playlist_id}"))?;
+                println!("⏬ Retrieving playlist {playlist_id} from {playlist_url}...");
+                let playlist: VideoPlaylist = self
+                    .cache
+                    .get_or_fetch(
+                        CacheKind::Channel,
+                        playlist_url.as_str(),
+                        fetch(self.client.clone(), playlist_url.clone()),
+                    )
+                    .await?;
+
+                (
+                    Channel::from(WithBaseUrl(playlist, base_url.clone())),
+                    format!("api/v1/video-playlists/{playlist_id}/videos"),
+                )
+            }
+            None => {
+                let channel_url = base_url.join(&format!("api/v1/video-channels/{name}"))?;
+                println!("⏬ Retrieving video channel {name} from {channel_url}...");
+                let video_channel: VideoChannel = self
+                    .cache
+                    .get_or_fetch(
+                        CacheKind::Channel,
+                        channel_url.as_str(),
+                        fetch(self.client.clone(), channel_url.clone()),
+                    )
+                    .await?;
+
+                (
+                    Channel::from(WithBaseUrl(video_channel, base_url.clone())),
+                    format!("api/v1/video-channels/{name}/videos"),
+                )
+            }
+        };
+
+        // The items of a channel are its videos; the files of each video are only available in
+        // the details of the video.
+        let mut videos_url = base_url.join(&videos_path)?;
+        println!("⏬ Retrieving videos from {videos_url}...");
+        let mut video_ids = Vec::with_capacity(limit.min(MAX_PAGE_SIZE));
+        while video_ids.len() < limit {
+            set_paging_query(&mut videos_url, video_ids.len(), limit - video_ids.len());
+            let videos_res: VideosResponse = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    videos_url.as_str(),
+                    fetch(self.client.clone(), videos_url.clone()),
+                )
+                .await?;
+            let count = videos_res.data.len();
+            video_ids.extend(videos_res.data.into_iter().filter_map(VideoRef::uuid));
+
+            if count == 0 || video_ids.len() >= videos_res.total {
+                break;
+            }
+        }
+
+        for video_id in video_ids.into_iter().take(limit) {
+            let video = self.video(&base_url, &video_id).await?;
+            if let Ok(item) = Item::try_from(WithBaseUrl(video, base_url.clone())) {
+                channel.items.push(item);
+            }
+        }
+        let video_categories = channel
+            .items
+            .iter()
+            .flat_map(|item| item.categories.keys().cloned());
+        channel.categories = channel_categories(video_categories);
+
+        Ok(channel)
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let (host, video_id) = download_key(file).ok_or(Error::NoRedirectUrlFound)?;
+        let base_url = self
+            .instance_url(&host)
+            .await?
+            .ok_or(Error::NoRedirectUrlFound)?;
+        let video = self.video(&base_url, &video_id).await?;
+
+        video
+            .preferred_file()
+            .map(|file| file.file_url.clone())
+            .ok_or(Error::NoRedirectUrlFound)
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // Any host can be a PeerTube instance, so check the API whether the video channel or
+        // playlist exists, unless the host is not allowed.
+        let Some(host) = url_host(url).filter(|host| is_valid_host(host)) else {
+            return Ok(None);
+        };
+        let Some(base_url) = self.instance_url(&host).await? else {
+            return Ok(None);
+        };
+        let (channel_id, api_path) = match path_segments(url)[..] {
+            ["c" | "video-channels", name, ..] => (
+                format!("{name}@{host}"),
+                format!("api/v1/video-channels/{name}"),
+            ),
+            ["w", "p", id] | ["videos", "watch", "playlist", id] => (
+                format!("{PLAYLIST_PREFIX}{id}@{host}"),
+                format!("api/v1/video-playlists/{id}"),
+            ),
+            _ => return Ok(None),
+        };
+        let api_url = base_url.join(&api_path)?;
+        let response = self.client.head(api_url).send().await;
+
+        Ok(response
+            .is_ok_and(|response| response.status().is_success())
+            .then_some(channel_id))
+    }
+}
+
+impl Backend {
+    /// Returns the base URL of the PeerTube instance at the provided host, if it is allowed.
+    ///
+    /// Hosts that are not public are only allowed if they match the configured URL patterns (see
+    /// [`is_allowed_url`]).
+    async fn instance_url(&self, host: &str) -> Result<Option<Url>> {
+        let base_url = Url::parse(&format!("https://{host}"))?;
+
+        Ok(is_allowed_url(&base_url, &self.url_patterns)
+            .await
+            .then_some(base_url))
+    }
+
+    /// Retrieves the details (including the files) of the video with the provided ID.
+    async fn video(&self, base_url: &Url, video_id: &str) -> Result<Video> {
+        let video_url = base_url.join(&format!("api/v1/videos/{video_id}"))?;
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::Items,
+                video_url.as_str(),
+                fetch(self.client.clone(), video_url.clone()),
+            )
+            .await
+    }
+}
+
+/// A PeerTube object with the base URL of the instance it belongs to.
+pub(crate) struct WithBaseUrl<T>(T, Url);
+
+/// A PeerTube video channel (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct VideoChannel {
+    /// The display name of the video channel.
+    pub(crate) display_name: String,
+
+    /// The description of the video channel.
+    pub(crate) description: Option<String>,
+
+    /// The original URL of the video channel.
+    pub(crate) url: Url,
+
+    /// The avatars of the video channel (in different sizes).
+    #[serde(default)]
+    pub(crate) avatars: Vec<Image>,
+
+    /// The account that owns the video channel.
+    pub(crate) owner_account: Account,
+}
+
+/// A PeerTube video playlist (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct VideoPlaylist {
+    /// The display name of the video playlist.
+    pub(crate) display_name: String,
+
+    /// The description of the video playlist.
+    pub(crate) description: Option<String>,
+
+    /// The original URL of the video playlist.
+    pub(crate) url: Url,
+
+    /// The (instance relative) path of the thumbnail of the video playlist.
+    pub(crate) thumbnail_path: Option<String>,
+
+    /// The account that owns the video playlist.
+    pub(crate) owner_account: Account,
+}
+
+/// A PeerTube account.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Account {
+    /// The display name of the account.
+    pub(crate) display_name: String,
+}
+
+/// A PeerTube image (avatar).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Image {
+    /// The (instance relative) path of the image.
+    pub(crate) path: String,
+
+    /// The width of the image.
+    pub(crate) width: u32,
+}
+
+/// The PeerTube videos response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VideosResponse {
+    /// The total number of videos.
+    total: usize,
+
+    /// The videos (or playlist elements) of the current page.
+    data: Vec<VideoRef>,
+}
+
+/// A reference to a PeerTube video, either directly or as a playlist element.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub(crate) enum VideoRef {
+    /// A video.
+    Video(VideoId),
+
+    /// A playlist element (the video can be unavailable).
+    Element {
+        /// The video of the playlist element.
+        video: Option<VideoId>,
+    },
+}
+
+impl VideoRef {
+    /// Returns the UUID of the referenced video (if available).
+    fn uuid(self) -> Option<String> {
+        match self {
+            VideoRef::Element { video } => video.map(|video| video.uuid),
+            VideoRef::Video(video) => Some(video.uuid),
+        }
+    }
+}
+
+/// The ID of a PeerTube video.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VideoId {
+    /// The UUID of the video.
+    uuid: String,
+}
+
+/// The details of a PeerTube video (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Video {
+    /// The UUID of the video.
+    pub(crate) uuid: String,
+
+    /// The name of the video.
+    pub(crate) name: String,
+
+    /// The description of the video.
+    pub(crate) description: Option<String>,
+
+    /// The duration of the video (in seconds).
+    pub(crate) duration: u32,
+
+    /// The category of the video (if any).
+    pub(crate) category: Option<Label>,
+
+    /// The tags of the video.
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+
+    /// The (instance relative) path of the thumbnail of the video.
+    pub(crate) thumbnail_path: Option<String>,
+
+    /// The (instance relative) path of the preview image of the video.
+    pub(crate) preview_path: Option<String>,
+
+    /// The time the video was published.
+    pub(crate) published_at: DateTime<Utc>,
+
+    /// The time the video was last updated.
+    pub(crate) updated_at: Option<DateTime<Utc>>,
+
+    /// The original URL of the video.
+    pub(crate) url: Url,
+
+    /// The (web video) files of the video.
+    #[serde(default)]
+    pub(crate) files: Vec<VideoFile>,
+
+    /// The streaming playlists of the video (with their files).
+    #[serde(default)]
+    pub(crate) streaming_playlists: Vec<StreamingPlaylist>,
+}
+
+impl Video {
+    /// Returns the preferred file of the video for a podcast.
+    ///
+    /// An audio-only file is preferred, otherwise the file with the lowest resolution is used.
+    fn preferred_file(&self) -> Option<&VideoFile> {
+        self.files
+            .iter()
+            .chain(
+                self.streaming_playlists
+                    .iter()
+                    .flat_map(|playlist| &playlist.files),
+            )
+            .min_by_key(|file| match file.resolution.id {
+                AUDIO_ONLY_RESOLUTION => 0,
+                resolution => resolution,
+            })
+    }
+}
+
+/// A PeerTube label (of a category).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Label {
+    /// The ID of the category.
+    pub(crate) id: Option<u32>,
+
+    /// The label of the category.
+    pub(crate) label: String,
+}
+
+/// A PeerTube streaming playlist.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct StreamingPlaylist {
+    /// The files of the streaming playlist.
+    #[serde(default)]
+    pub(crate) files: Vec<VideoFile>,
+}
+
+/// A PeerTube video file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct VideoFile {
+    /// The resolution of the video file.
+    pub(crate) resolution: Resolution,
+
+    /// The size of the video file (in bytes).
+    pub(crate) size: u64,
+
+    /// The static URL of the video file.
+    pub(crate) file_url: String,
+}
+
+/// A PeerTube video file resolution.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Resolution {
+    /// The ID of the resolution, i.e. the height in pixels (0 for audio-only).
+    pub(crate) id: u32,
+}
+
+impl From<WithBaseUrl<VideoChannel>> for Channel {
+    fn from(WithBaseUrl(video_channel, base_url): WithBaseUrl<VideoChannel>) -> Self {
+        let image = video_channel
+            .avatars
+            .iter()
+            .max_by_key(|avatar| avatar.width)
+            .and_then(|avatar| base_url.join(&avatar.path).ok());
+        // The items are fetched (and cached) separately and the categories are derived from them.
+        let categories = Vec::new();
+        let items = Vec::new();
+
+        Channel {
+            title: format!("{0} (via PeerTube)", video_channel.display_name),
+            link: video_channel.url,
+            description: video_channel.description.unwrap_or_default(),
+            author: Some(video_channel.owner_account.display_name),
+            categories,
+            image,
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl From<WithBaseUrl<VideoPlaylist>> for Channel {
+    fn from(WithBaseUrl(playlist, base_url): WithBaseUrl<VideoPlaylist>) -> Self {
+        let image = playlist
+            .thumbnail_path
+            .and_then(|path| base_url.join(&path).ok());
+        // The items are fetched (and cached) separately and the categories are derived from them.
+        let categories = Vec::new();
+        let items = Vec::new();
+
+        Channel {
+            title: format!("{0} (via PeerTube)", playlist.display_name),
+            link: playlist.url,
+            description: playlist.description.unwrap_or_default(),
+            author: Some(playlist.owner_account.display_name),
+            categories,
+            image,
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl TryFrom<WithBaseUrl<Video>> for Item {
+    type Error = Error;
+
+    fn try_from(WithBaseUrl(video, base_url): WithBaseUrl<Video>) -> Result<Self> {
+        let video_file = video.preferred_file().ok_or(Error::NoRedirectUrlFound)?;
+        let extension = Path::new(video_file.file_url.as_str())
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("mp4"));
+        let mime_type = mime_db::lookup(&extension).unwrap_or(DEFAULT_FILE_TYPE);
+        // Audio-only files have an MP4 container but only contain audio.
+        let mime_type = if video_file.resolution.id == AUDIO_ONLY_RESOLUTION {
+            mime_type.replace("video/", "audio/")
+        } else {
+            String::from(mime_type)
+        };
+        let mut file = PathBuf::from(url_host(&base_url).unwrap_or_default());
+        file.push(&video.uuid);
+        file.set_extension(extension);
+        let enclosure = Enclosure {
+            file,
+            mime_type,
+            length: video_file.size,
+        };
+
+        let description = video
+            .description
+            .as_ref()
+            .map(|description| format!("{description}\n\nTaken from PeerTube: {}", video.url))
+            .unwrap_or_else(|| format!("Taken from PeerTube: {}", video.url));
+        let categories = video
+            .category
+            .iter()
+            .filter_map(|category| {
+                let mut url = base_url.join("search").ok()?;
+                if let Some(id) = category.id {
+                    url.query_pairs_mut()
+                        .append_pair("categoryOneOf", &id.to_string());
+                }
+
+                Some((category.label.clone(), url))
+            })
+            .collect();
+        let image = video
+            .preview_path
+            .as_ref()
+            .or(video.thumbnail_path.as_ref())
+            .and_then(|path| base_url.join(path).ok());
+
+        Ok(Item {
+            title: video.name,
+            link: video.url,
+            description: Some(description),
+            categories,
+            enclosure,
+            duration: Some(video.duration),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            guid: video.uuid,
+            keywords: video.tags,
+            season: None,
+            episode: None,
+            image,
+            published_at: video.published_at,
+            updated_at: video.updated_at.unwrap_or(video.published_at),
+        })
+    }
+}
+
+/// Returns the host of the provided URL, including the port if it is not the default one.
+fn url_host(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+
+    match url.port() {
+        Some(port) => Some(format!("{host}:{port}")),
+        None => Some(String::from(host)),
+    }
+}
+
+/// Returns whether the provided host (with an optional port) is valid.
+fn is_valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'))
+}
+
+/// Returns the instance host and video ID for the provided download file path.
+fn download_key(file: &Path) -> Option<(String, String)> {
+    let host = file.parent()?.to_str().filter(|host| is_valid_host(host))?;
+    let video_id = file.file_stem()?.to_str()?;
+
+    Some((host.to_owned(), video_id.to_owned()))
+}
+
+/// Fetches a response from the PeerTube API URL.
+async fn fetch<T: DeserializeOwned>(client: reqwest::Client, url: Url) -> Result<T> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    let result = response.json().await?;
+
+    Ok(result)
+}
+
+/// Set paging query pairs for URL.
+///
+/// The count is capped to the maximum page size. Another request will be necessary to retrieve
+/// more.
+fn set_paging_query(url: &mut Url, start: usize, count: usize) {
+    url.query_pairs_mut()
+        .clear()
+        .append_pair("start", &format!("{start}"))
+        .append_pair("count", &format!("{}", count.min(MAX_PAGE_SIZE)));
+}
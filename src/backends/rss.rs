@@ -0,0 +1,357 @@
+//! The RSS back-end.
+//!
+//! It retrieves and parses an existing (upstream) RSS feed to re-publish it, so that it can
+//! benefit from caching, limits and the download modes and transcoding. The channel ID is the
+//! URL of the feed.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use ::rss::extension::itunes::{ITunesChannelExtension, ITunesItemExtension};
+use ::rss::extension::Extension;
+use ::rss::{Channel as RssChannel, Item as RssItem};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Url;
+
+use super::{
+    file_url, is_allowed_url, mime_extension, url_file, BackendConfig, BackendInfo, Channel,
+    Chapter, Enclosure, Item, Medium, DEFAULT_CATEGORY,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::chapters::parse_timestamp;
+use crate::{Error, Result};
+
+/// The default file (MIME) type used if an enclosure does not specify one.
+const DEFAULT_FILE_TYPE: &str = "audio/mpeg";
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The maximum size of an upstream feed (in bytes).
+const MAX_FEED_SIZE: usize = 16 * 1024 * 1024;
+
+/// The information of the RSS back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "rss",
+    name: "RSS",
+    description: "URL-encoded URL of an existing feed",
+    url_patterns: &[],
+};
+
+/// Creates an RSS back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        url_patterns: settings.url_patterns.clone(),
+        cache: cache.scoped("rss", settings.ttl),
+    }
+}
+
+/// The RSS back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The configured URL patterns of the feeds that are allowed even if they are not public.
+    url_patterns: Vec<String>,
+
+    /// The cache store used for feeds.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "RSS"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For RSS a channel ID is the URL of the upstream feed.
+        let url = Url::parse(channel_id)?;
+        if !is_allowed_url(&url, &self.url_patterns).await {
+            return Err(Error::UnsupportedChannel(channel_id.to_string()));
+        }
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+
+        // The channel and its items are retrieved at once, so they are cached as items.
+        println!("⏬ Retrieving feed from {url}...");
+        let mut channel = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Items,
+                url.as_str(),
+                fetch_feed(self.client.clone(), self.cache.clone(), url.clone()),
+            )
+            .await?;
+        // Feeds are not necessarily ordered by publication date, so make sure the most recent items
+        // are kept.
+        channel
+            .items
+            .sort_by_key(|item| std::cmp::Reverse(item.published_at));
+        channel.items.truncate(limit);
+
+        Ok(channel)
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        // The download file path encodes the upstream enclosure URL. Only the enclosure URLs of
+        // upstream feeds that have been retrieved are accepted, so that no arbitrary URLs are
+        // redirected to, proxied or transcoded.
+        let url = file_url(file).ok_or(Error::NoRedirectUrlFound)?;
+        if !is_allowed_url(&url, &self.url_patterns).await {
+            return Err(Error::NoRedirectUrlFound);
+        }
+
+        self.cache
+            .peek(CacheKind::RedirectUrl, url.as_str())?
+            .ok_or(Error::NoRedirectUrlFound)
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // Any URL can be the URL of a feed, so check whether it can be retrieved and parsed.
+        // Only URLs that match the configured URL patterns are resolved.
+        if !is_allowed_url(url, &self.url_patterns).await {
+            return Ok(None);
+        }
+        let channel = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Items,
+                url.as_str(),
+                fetch_feed(self.client.clone(), self.cache.clone(), url.clone()),
+            )
+            .await;
+
+        Ok(channel.is_ok().then(|| url.to_string()))
+    }
+}
+
+/// An RSS item with its position in, and the link and timestamp of the feed it belongs to.
+pub(crate) struct IndexedItem<'a>(usize, &'a RssItem, &'a Url, DateTime<Utc>);
+
+impl TryFrom<RssChannel> for Channel {
+    type Error = Error;
+
+    fn try_from(rss_channel: RssChannel) -> Result<Self> {
+        let link = Url::parse(rss_channel.link())?;
+        let itunes_ext = rss_channel.itunes_ext();
+        let author = itunes_ext.and_then(ITunesChannelExtension::author);
+        let mut categories = rss_channel
+            .categories()
+            .iter()
+            .map(|category| category.name().to_string())
+            .collect::<Vec<_>>();
+        if let Some(itunes_ext) = itunes_ext {
+            categories.extend(
+                itunes_ext
+                    .categories()
+                    .iter()
+                    .map(|category| category.text().to_string()),
+            );
+        }
+        if categories.is_empty() {
+            categories.push(String::from(DEFAULT_CATEGORY));
+        }
+        let image = itunes_ext
+            .and_then(ITunesChannelExtension::image)
+            .or_else(|| rss_channel.image().map(|image| image.url()))
+            .and_then(|image| Url::parse(image).ok());
+        let medium = rss_channel
+            .extensions()
+            .get("podcast")
+            .and_then(|podcast_ext| podcast_ext.get("medium"))
+            .and_then(|extensions| extensions.first())
+            .and_then(|extension| extension.value())
+            .and_then(Medium::parse)
+            .unwrap_or_default();
+        let timestamp = rss_channel
+            .last_build_date()
+            .or(rss_channel.pub_date())
+            .and_then(parse_date)
+            .unwrap_or(DateTime::UNIX_EPOCH);
+        let items = rss_channel
+            .items()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rss_item)| {
+                Item::try_from(IndexedItem(index, rss_item, &link, timestamp)).ok()
+            })
+            .collect();
+
+        Ok(Channel {
+            title: format!("{0} (via RSS)", rss_channel.title()),
+            link,
+            description: rss_channel.description().to_string(),
+            author: author.map(String::from),
+            categories,
+            image,
+            images: BTreeMap::new(),
+            medium,
+            items,
+        })
+    }
+}
+
+impl TryFrom<IndexedItem<'_>> for Item {
+    type Error = Error;
+
+    fn try_from(
+        IndexedItem(index, rss_item, channel_link, channel_timestamp): IndexedItem<'_>,
+    ) -> Result<Self> {
+        let rss_enclosure = rss_item.enclosure().ok_or(Error::NoRedirectUrlFound)?;
+        let enclosure_url = Url::parse(rss_enclosure.url())?;
+        if !matches!(enclosure_url.scheme(), "http" | "https") {
+            return Err(Error::NoRedirectUrlFound);
+        }
+        let mime_type = Some(rss_enclosure.mime_type())
+            .filter(|mime_type| !mime_type.is_empty())
+            .unwrap_or(DEFAULT_FILE_TYPE);
+        let extension = Path::new(enclosure_url.path())
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .or_else(|| mime_extension(mime_type))
+            .unwrap_or_default();
+        let enclosure = Enclosure {
+            file: url_file(enclosure_url.as_str(), extension),
+            mime_type: String::from(mime_type),
+            length: rss_enclosure.length().parse().unwrap_or_default(),
+        };
+
+        let itunes_ext = rss_item.itunes_ext();
+        let link = rss_item
+            .link()
+            .and_then(|link| Url::parse(link).ok())
+            .unwrap_or_else(|| channel_link.clone());
+        let description = rss_item
+            .description()
+            .or_else(|| itunes_ext.and_then(ITunesItemExtension::summary))
+            .map(String::from);
+        let categories = rss_item
+            .categories()
+            .iter()
+            .filter_map(|category| {
+                let url = Url::parse(category.domain()?).ok()?;
+
+                Some((category.name().to_string(), url))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let duration = itunes_ext
+            .and_then(ITunesItemExtension::duration)
+            .and_then(parse_timestamp);
+        let guid = rss_item
+            .guid()
+            .map(|guid| guid.value().to_string())
+            .unwrap_or_else(|| enclosure_url.to_string());
+        let keywords = itunes_ext
+            .and_then(ITunesItemExtension::keywords)
+            .map(|keywords| {
+                keywords
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let chapters = rss_item
+            .extensions()
+            .get("psc")
+            .and_then(|psc_ext| psc_ext.get("chapters"))
+            .and_then(|extensions| extensions.first())
+            .and_then(|extension| extension.children().get("chapter"))
+            .map(|extensions| extensions.iter().filter_map(psc_chapter).collect())
+            .unwrap_or_default();
+        let season = itunes_ext
+            .and_then(ITunesItemExtension::season)
+            .and_then(|season| season.trim().parse().ok());
+        let episode = itunes_ext
+            .and_then(ITunesItemExtension::episode)
+            .and_then(|episode| episode.trim().parse().ok());
+        let image = itunes_ext
+            .and_then(ITunesItemExtension::image)
+            .and_then(|image| Url::parse(image).ok());
+        // Items without a (valid) publication date get one based on their position in the feed
+        // relative to the feed timestamp to keep them in order, so that it is stable.
+        let published_at = rss_item
+            .pub_date()
+            .and_then(parse_date)
+            .unwrap_or_else(|| channel_timestamp - Duration::seconds(index as i64));
+        // There is no updated at timestamp available, really.
+        let updated_at = published_at;
+
+        Ok(Item {
+            title: rss_item.title().unwrap_or("Untitled").to_string(),
+            link,
+            description,
+            categories,
+            enclosure,
+            duration,
+            chapters,
+            transcripts: Vec::new(),
+            guid,
+            keywords,
+            season,
+            episode,
+            image,
+            published_at,
+            updated_at,
+        })
+    }
+}
+
+/// Parses an RFC 2822 date as used in RSS feeds.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Parses a Podlove Simple Chapters chapter element.
+///
+/// The start time is a normal play time (`[[HH:]MM:]SS[.mmm]`), of which the fraction is ignored.
+fn psc_chapter(extension: &Extension) -> Option<Chapter> {
+    let start_time = parse_timestamp(extension.attrs().get("start")?)?;
+    let title = extension.attrs().get("title")?.trim();
+
+    Some(Chapter {
+        start_time,
+        title: String::from(title),
+    })
+}
+
+/// Fetches and parses the feed at the provided URL.
+///
+/// The enclosure URLs of the items are stored in the cache as redirect URLs, so that they are
+/// known when the media content is downloaded. Feeds larger than [`MAX_FEED_SIZE`] are refused.
+async fn fetch_feed(client: reqwest::Client, cache: Cache, url: Url) -> Result<Channel> {
+    let mut response = client.get(url.clone()).send().await?.error_for_status()?;
+    let too_large = || Error::FeedTooLarge(url.to_string());
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_FEED_SIZE as u64)
+    {
+        return Err(too_large());
+    }
+    // The content length is not always known (or correct), so limit the body while reading it.
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > MAX_FEED_SIZE {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let mut rss_channel = RssChannel::read_from(&bytes[..])?;
+    // Fall back to the URL of the feed itself if it has no (valid) link.
+    if Url::parse(rss_channel.link()).is_err() {
+        rss_channel.set_link(url.as_str());
+    }
+    let channel = Channel::try_from(rss_channel)?;
+    for item in &channel.items {
+        if let Some(url) = file_url(&item.enclosure.file) {
+            cache.store(CacheKind::RedirectUrl, url.as_str(), &url.to_string())?;
+        }
+    }
+
+    Ok(channel)
+}
@@ -0,0 +1,821 @@
+//! The SoundCloud back-end.
+//!
+//! It uses the (undocumented) SoundCloud API v2 that is also used by the SoundCloud website to
+//! retrieve the feed (user or playlist/set) and items (tracks).
+//! The API requires a client ID, which is taken from the scripts of the SoundCloud website.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{StatusCode, Url};
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+use youtube_dl::YoutubeDlOutput;
+
+use super::{
+    channel_categories, mime_extension, path_segments, BackendConfig, BackendInfo, Channel,
+    Enclosure, Item, Medium, YoutubeDlOptions,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::{Error, Result};
+
+/// The base URL for the SoundCloud API.
+const API_BASE_URL: &str = "https://api-v2.soundcloud.com";
+
+/// The base URL of the SoundCloud website (also used for downloading SoundCloud files).
+///
+/// The canonical URLs of users and playlists based on this URL are resolved by the API.
+const WEBSITE_BASE_URL: &str = "https://soundcloud.com";
+
+/// The prefix of the URLs of the scripts of the SoundCloud website.
+const SCRIPT_URL_PREFIX: &str = "https://a-v2.sndcdn.com/assets/";
+
+/// The cache key used for the client ID.
+///
+/// It cannot clash with the API URLs that are used as keys for the other channel entries.
+const CLIENT_ID_KEY: &str = "client_id";
+
+/// The paths of SoundCloud URLs that do not refer to users.
+const RESERVED_PATHS: [&str; 6] = ["charts", "discover", "search", "stream", "tags", "upload"];
+
+/// The default bitrate used by SoundCloud.
+const DEFAULT_BITRATE: u64 = 128 * 1024;
+
+/// The default file (MIME) type used by SoundCloud.
+const DEFAULT_FILE_TYPE: &str = "audio/mpeg";
+
+/// The default page size.
+const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// The maximum number of tracks that can be retrieved at once by their IDs.
+const MAX_TRACK_IDS: usize = 50;
+
+/// The information of the SoundCloud back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "soundcloud",
+    name: "SoundCloud",
+    description:
+        "SoundCloud username or playlist path with %2F for slashes, e.g. user%2Fsets%2Fname",
+    url_patterns: &["https://soundcloud.com/*"],
+};
+
+/// Creates a SoundCloud back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        api_base_url: settings.base_url_or("api", API_BASE_URL),
+        website_base_url: settings.base_url_or("website", WEBSITE_BASE_URL),
+        youtube_dl: settings.youtube_dl_options(),
+        cache: cache.scoped("soundcloud", settings.ttl),
+    }
+}
+
+/// The SoundCloud back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The base URL for the SoundCloud API.
+    api_base_url: Url,
+
+    /// The base URL of the SoundCloud website (for the client ID and downloading files).
+    website_base_url: Url,
+
+    /// The options used for running `yt-dlp`.
+    youtube_dl: YoutubeDlOptions,
+
+    /// The cache store used for the client ID, users, playlists, tracks and redirect URLs.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "SoundCloud"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For SoundCloud a channel ID is either a user name (`user`) or a playlist/set of a user
+        // (`user/sets/name`), i.e. the path of the SoundCloud URL.
+        let limit = item_limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        // SoundCloud rotates its client IDs, so if the cached one is rejected, retrieve a new
+        // one and try again (once).
+        match self.fetch_channel(channel_id, limit).await {
+            Err(Error::ClientIdRejected) => {
+                eprintln!("⚠️ SoundCloud client ID was rejected, retrieving a new one...");
+                self.cache.invalidate(CacheKind::Channel, CLIENT_ID_KEY)?;
+
+                self.fetch_channel(channel_id, limit).await
+            }
+            result => result,
+        }
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let key = download_key(file);
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                &key,
+                retrieve_redirect_url(
+                    self.youtube_dl.clone(),
+                    self.website_base_url.clone(),
+                    key.clone(),
+                ),
+            )
+            .await
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // The path of a user or playlist/set URL is the channel ID.
+        let channel_id = match path_segments(url)[..] {
+            [user, "sets", name, ..] => Some(format!("{user}/sets/{name}")),
+            [user, ..] if !RESERVED_PATHS.contains(&user) => Some(String::from(user)),
+            _ => None,
+        };
+
+        Ok(channel_id)
+    }
+}
+
+impl Backend {
+    /// Retrieves the user or playlist with (at most the limit of) its tracks.
+    async fn fetch_channel(&self, channel_id: &str, limit: usize) -> Result<Channel> {
+        let client_id = self.client_id().await?;
+        let mut resolve_url = self.api_base_url.clone();
+        resolve_url.set_path("resolve");
+        resolve_url
+            .query_pairs_mut()
+            .append_pair("url", &format!("{WEBSITE_BASE_URL}/{channel_id}"));
+
+        println!("⏬ Retrieving user or playlist {channel_id} from {resolve_url}...");
+        let resource: Resource = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                resolve_url.as_str(),
+                fetch(self.client.clone(), resolve_url.clone(), client_id.clone()),
+            )
+            .await?;
+
+        match resource {
+            Resource::User(user) => {
+                let tracks = self.user_tracks(&user, limit, &client_id).await?;
+
+                Ok(Channel::from(UserWithTracks(*user, tracks)))
+            }
+            Resource::Playlist(playlist) => {
+                let track_ids = playlist
+                    .tracks
+                    .iter()
+                    .take(limit)
+                    .map(|track| track.id)
+                    .collect::<Vec<_>>();
+                let tracks = self.tracks(&track_ids, &client_id).await?;
+
+                Ok(Channel::from(PlaylistWithTracks(*playlist, tracks)))
+            }
+            Resource::Other => Err(Error::UnsupportedChannel(channel_id.to_string())),
+        }
+    }
+
+    /// Returns the client ID to use for the SoundCloud API.
+    async fn client_id(&self) -> Result<String> {
+        self.cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                CLIENT_ID_KEY,
+                fetch_client_id(self.client.clone(), self.website_base_url.clone()),
+            )
+            .await
+    }
+
+    /// Retrieves (at most the limit of) the tracks of the user.
+    async fn user_tracks(&self, user: &User, limit: usize, client_id: &str) -> Result<Vec<Track>> {
+        let mut limit = limit;
+        let mut tracks_url = self.api_base_url.clone();
+        tracks_url.set_path(&format!("users/{}/tracks", user.id));
+        println!(
+            "⏬ Retrieving tracks of user {} from {tracks_url}...",
+            user.username
+        );
+
+        set_paging_query(&mut tracks_url, limit);
+        let mut tracks = Vec::with_capacity(limit.min(DEFAULT_PAGE_SIZE));
+        loop {
+            let tracks_res: TracksResponse = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    tracks_url.as_str(),
+                    fetch(
+                        self.client.clone(),
+                        tracks_url.clone(),
+                        client_id.to_owned(),
+                    ),
+                )
+                .await?;
+            let count = tracks_res.collection.len();
+            tracks.extend(tracks_res.collection);
+
+            // Continue onto the next URL, if there is one and the limit was not reached.
+            limit = limit.saturating_sub(count);
+            match (limit, tracks_res.next_href) {
+                (0, _) | (_, None) => break,
+                (_, Some(_)) if count == 0 => break,
+                (_, Some(next_url)) => {
+                    tracks_url = Url::parse(&next_url)?;
+                    set_paging_query(&mut tracks_url, limit);
+                }
+            }
+        }
+        tracks.retain(Track::is_playable);
+
+        Ok(tracks)
+    }
+
+    /// Retrieves the tracks with the provided IDs, in the same order.
+    ///
+    /// Tracks that are no longer available are skipped.
+    async fn tracks(&self, track_ids: &[u64], client_id: &str) -> Result<Vec<Track>> {
+        let mut tracks = Vec::with_capacity(track_ids.len());
+        for chunk in track_ids.chunks(MAX_TRACK_IDS) {
+            let ids = chunk
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            let mut tracks_url = self.api_base_url.clone();
+            tracks_url.set_path("tracks");
+            tracks_url.query_pairs_mut().append_pair("ids", &ids);
+            println!("⏬ Retrieving {} tracks from {tracks_url}...", chunk.len());
+
+            let mut chunk_tracks: Vec<Track> = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    tracks_url.as_str(),
+                    fetch(
+                        self.client.clone(),
+                        tracks_url.clone(),
+                        client_id.to_owned(),
+                    ),
+                )
+                .await?;
+            // The API does not return the tracks in the requested order.
+            chunk_tracks.sort_by_key(|track| chunk.iter().position(|id| *id == track.id));
+            tracks.extend(chunk_tracks);
+        }
+        tracks.retain(Track::is_playable);
+
+        Ok(tracks)
+    }
+}
+
+/// A SoundCloud user with its tracks.
+pub(crate) struct UserWithTracks(User, Vec<Track>);
+
+/// A SoundCloud playlist with its tracks.
+pub(crate) struct PlaylistWithTracks(Playlist, Vec<Track>);
+
+/// A resolved SoundCloud resource (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", tag = "kind", rename_all = "lowercase")]
+pub(crate) enum Resource {
+    /// A user.
+    User(Box<User>),
+
+    /// A playlist (or set, album, etc.).
+    Playlist(Box<Playlist>),
+
+    /// Any other kind of resource, e.g. a single track.
+    #[serde(other)]
+    Other,
+}
+
+/// A SoundCloud user.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct User {
+    /// The ID of the user.
+    pub(crate) id: u64,
+
+    /// The (display) name of the user.
+    pub(crate) username: String,
+
+    /// The description (bio) of the user.
+    pub(crate) description: Option<String>,
+
+    /// The URL of the avatar of the user.
+    pub(crate) avatar_url: Option<Url>,
+
+    /// The original URL of the user.
+    pub(crate) permalink_url: Url,
+}
+
+/// A SoundCloud playlist.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Playlist {
+    /// The title of the playlist.
+    pub(crate) title: String,
+
+    /// The description of the playlist.
+    pub(crate) description: Option<String>,
+
+    /// The URL of the artwork of the playlist.
+    pub(crate) artwork_url: Option<Url>,
+
+    /// The original URL of the playlist.
+    pub(crate) permalink_url: Url,
+
+    /// The genre of the playlist.
+    pub(crate) genre: Option<String>,
+
+    /// The user that created the playlist.
+    pub(crate) user: User,
+
+    /// The (references to the) tracks of the playlist.
+    ///
+    /// Only the first few tracks are complete, so all tracks are retrieved by their IDs.
+    pub(crate) tracks: Vec<TrackRef>,
+}
+
+/// A reference to a SoundCloud track.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct TrackRef {
+    /// The ID of the track.
+    pub(crate) id: u64,
+}
+
+/// The SoundCloud tracks response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct TracksResponse {
+    /// The contained tracks.
+    collection: Vec<Track>,
+
+    /// The API URL of the next page (if any).
+    next_href: Option<String>,
+}
+
+/// A SoundCloud track.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Track {
+    /// The ID of the track.
+    pub(crate) id: u64,
+
+    /// The title of the track.
+    pub(crate) title: String,
+
+    /// The description of the track.
+    pub(crate) description: Option<String>,
+
+    /// The genre of the track.
+    pub(crate) genre: Option<String>,
+
+    /// The tags of the track (separated by spaces, tags with spaces are quoted).
+    #[serde(default)]
+    pub(crate) tag_list: String,
+
+    /// The URL of the artwork of the track.
+    pub(crate) artwork_url: Option<Url>,
+
+    /// The user that posted the track.
+    pub(crate) user: User,
+
+    /// The time the track was created.
+    pub(crate) created_at: DateTime<Utc>,
+
+    /// The time the track was last modified.
+    pub(crate) last_modified: DateTime<Utc>,
+
+    /// The original URL of the track.
+    pub(crate) permalink_url: Url,
+
+    /// The length of the track (in milliseconds).
+    pub(crate) full_duration: u64,
+
+    /// The policy of the track, e.g. whether it is only available as a snippet.
+    pub(crate) policy: Option<String>,
+}
+
+impl Track {
+    /// Returns whether the full track can be played (without subscription).
+    fn is_playable(&self) -> bool {
+        !matches!(self.policy.as_deref(), Some("BLOCK" | "SNIP"))
+    }
+
+    /// Returns the tags of the track.
+    fn tags(&self) -> Vec<String> {
+        self.tag_list
+            .split('"')
+            .enumerate()
+            .flat_map(|(index, part)| {
+                // Every odd part is a quoted tag.
+                if index % 2 == 1 {
+                    vec![part]
+                } else {
+                    part.split_whitespace().collect()
+                }
+            })
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl From<UserWithTracks> for Channel {
+    fn from(UserWithTracks(user, tracks): UserWithTracks) -> Self {
+        let items = tracks.into_iter().map(Item::from).collect::<Vec<_>>();
+        let genres = items
+            .iter()
+            .flat_map(|item| item.categories.keys().cloned());
+        let categories = channel_categories(genres);
+
+        Channel {
+            title: format!("{0} (via SoundCloud)", user.username),
+            link: user.permalink_url,
+            description: user.description.unwrap_or_default(),
+            author: Some(user.username),
+            categories,
+            image: user.avatar_url.map(large_artwork_url),
+            images: BTreeMap::new(),
+            medium: Medium::Music,
+            items,
+        }
+    }
+}
+
+impl From<PlaylistWithTracks> for Channel {
+    fn from(PlaylistWithTracks(playlist, tracks): PlaylistWithTracks) -> Self {
+        let items = tracks.into_iter().map(Item::from).collect::<Vec<_>>();
+        let genres = items
+            .iter()
+            .flat_map(|item| item.categories.keys().cloned());
+        let categories = channel_categories(playlist.genre.into_iter().chain(genres));
+        let image = playlist.artwork_url.or(playlist.user.avatar_url);
+
+        Channel {
+            title: format!("{0} (via SoundCloud)", playlist.title),
+            link: playlist.permalink_url,
+            description: playlist.description.unwrap_or_default(),
+            author: Some(playlist.user.username),
+            categories,
+            image: image.map(large_artwork_url),
+            images: BTreeMap::new(),
+            medium: Medium::Music,
+            items,
+        }
+    }
+}
+
+impl From<Track> for Item {
+    fn from(track: Track) -> Self {
+        let mut file = PathBuf::from(track.permalink_url.path().trim_matches('/'));
+        let extension = mime_extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
+        file.set_extension(extension);
+
+        let keywords = track.tags();
+        let description = track
+            .description
+            .clone()
+            .filter(|description| !description.is_empty())
+            .unwrap_or_else(|| format!("Taken from SoundCloud: {0}", track.permalink_url));
+        let categories = track
+            .genre
+            .iter()
+            .filter(|genre| !genre.is_empty())
+            .map(|genre| {
+                let mut url = Url::parse(WEBSITE_BASE_URL).expect("URL can always be parsed");
+                url.path_segments_mut()
+                    .expect("URL can be a base")
+                    .extend(["tags", genre]);
+
+                (genre.clone(), url)
+            })
+            .collect();
+        let duration = (track.full_duration / 1000) as u32;
+        let enclosure = Enclosure {
+            file,
+            mime_type: String::from(DEFAULT_FILE_TYPE),
+            length: estimated_file_size(duration),
+        };
+        let image = track.artwork_url.or(track.user.avatar_url);
+
+        Item {
+            title: track.title,
+            link: track.permalink_url,
+            description: Some(description),
+            categories,
+            enclosure,
+            duration: Some(duration),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            guid: track.id.to_string(),
+            keywords,
+            season: None,
+            episode: None,
+            image: image.map(large_artwork_url),
+            published_at: track.created_at,
+            updated_at: track.last_modified,
+        }
+    }
+}
+
+/// Returns the URL of the large (500x500) variant of the provided artwork URL.
+fn large_artwork_url(url: Url) -> Url {
+    let large_url = url.as_str().replace("-large.", "-t500x500.");
+
+    Url::parse(&large_url).unwrap_or(url)
+}
+
+/// Returns the download key (track path) for the provided download file path.
+fn download_key(file: &Path) -> String {
+    format!("/{}", file.with_extension("").to_string_lossy())
+}
+
+/// Returns the estimated file size in bytes for a given duration.
+///
+/// It uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
+fn estimated_file_size(duration: u32) -> u64 {
+    DEFAULT_BITRATE * duration as u64 / 8
+}
+
+/// Fetches the client ID from the scripts of the SoundCloud website.
+///
+/// The client ID is usually found in one of the last scripts, so these are tried first.
+async fn fetch_client_id(client: reqwest::Client, website_base_url: Url) -> Result<String> {
+    println!("🔑 Retrieving SoundCloud client ID from {website_base_url}...");
+    let html = client
+        .get(website_base_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let script_urls = html
+        .split("src=\"")
+        .skip(1)
+        .filter_map(|part| part.split_once('"').map(|(url, _)| url))
+        .filter(|url| url.starts_with(SCRIPT_URL_PREFIX) && url.ends_with(".js"))
+        .collect::<Vec<_>>();
+
+    for script_url in script_urls.into_iter().rev() {
+        let script = client
+            .get(script_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        if let Some(client_id) = find_client_id(&script) {
+            return Ok(client_id);
+        }
+    }
+
+    Err(Error::NoClientIdFound)
+}
+
+/// Finds the client ID in the provided script (if present).
+fn find_client_id(script: &str) -> Option<String> {
+    script
+        .match_indices("client_id")
+        .filter_map(|(index, pattern)| {
+            let rest = script[index + pattern.len()..].strip_prefix([':', '='])?;
+            let rest = rest.strip_prefix('"').unwrap_or(rest);
+            let client_id = rest
+                .chars()
+                .take_while(char::is_ascii_alphanumeric)
+                .collect::<String>();
+
+            (client_id.len() == 32).then_some(client_id)
+        })
+        .next()
+}
+
+/// Fetches a response from the SoundCloud API URL using the provided client ID.
+///
+/// If the API rejects the client ID, [`Error::ClientIdRejected`] is returned.
+async fn fetch<T: DeserializeOwned>(
+    client: reqwest::Client,
+    mut url: Url,
+    client_id: String,
+) -> Result<T> {
+    url.query_pairs_mut().append_pair("client_id", &client_id);
+    let response = client.get(url).send().await?;
+    if matches!(
+        response.status(),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    ) {
+        return Err(Error::ClientIdRejected);
+    }
+    let result = response.error_for_status()?.json().await?;
+
+    Ok(result)
+}
+
+/// Set paging query pairs for URL.
+///
+/// The limit is capped to the default page size. Another request will be necessary to retrieve
+/// more. The offset is kept as-is, since it is provided by the API in the next page URLs.
+fn set_paging_query(url: &mut Url, limit: usize) {
+    let pairs = url
+        .query_pairs()
+        .filter(|(key, _)| !matches!(key.as_ref(), "client_id" | "limit" | "linked_partitioning"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair("limit", &format!("{}", limit.min(DEFAULT_PAGE_SIZE)))
+        .append_pair("linked_partitioning", "1");
+}
+
+/// Retrieves the redirect URL for the provided SoundCloud track key.
+async fn retrieve_redirect_url(
+    youtube_dl: YoutubeDlOptions,
+    website_base_url: Url,
+    download_key: String,
+) -> Result<String> {
+    let mut url = website_base_url;
+    url.set_path(&download_key);
+
+    println!("🌍 Determining direct URL for {download_key}...");
+    // Select the progressive MP3 format that is directly available (so no HLS), which is the
+    // format that is (almost) always available.
+    let output = youtube_dl
+        .command(url)
+        .format("http_mp3_128/http_mp3_1_0/bestaudio[ext=mp3][protocol^=http]")
+        .run_async()
+        .await?;
+
+    match output {
+        YoutubeDlOutput::SingleVideo(yt_item) => yt_item.url.ok_or(Error::NoRedirectUrlFound),
+        _ => Err(Error::NoRedirectUrlFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::serde::json::serde_json;
+
+    use super::*;
+
+    /// Reads the recorded fixture with the provided name.
+    fn read_fixture(name: &str) -> String {
+        let path = format!(
+            "{}/tests/fixtures/soundcloud/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        std::fs::read_to_string(path).expect("fixture exists")
+    }
+
+    /// Deserializes the recorded API response fixture with the provided name.
+    fn fixture<T: DeserializeOwned>(name: &str) -> T {
+        serde_json::from_str(&read_fixture(name)).expect("fixture can be deserialized")
+    }
+
+    #[test]
+    fn resolve_user() {
+        let resource: Resource = fixture("resolve_user.json");
+        let Resource::User(user) = resource else {
+            panic!("resource is not a user: {resource:?}");
+        };
+
+        assert_eq!(user.id, 87654321);
+        assert_eq!(user.username, "My Favourite DJ");
+        assert_eq!(
+            user.permalink_url.as_str(),
+            "https://soundcloud.com/myfavouritedj"
+        );
+        assert!(user.avatar_url.is_some());
+    }
+
+    #[test]
+    fn resolve_playlist() {
+        let resource: Resource = fixture("resolve_playlist.json");
+        let Resource::Playlist(playlist) = resource else {
+            panic!("resource is not a playlist: {resource:?}");
+        };
+
+        assert_eq!(playlist.title, "Summer Sets");
+        assert_eq!(playlist.user.username, "My Favourite DJ");
+        assert!(playlist.artwork_url.is_none());
+        let track_ids = playlist
+            .tracks
+            .iter()
+            .map(|track| track.id)
+            .collect::<Vec<_>>();
+        assert_eq!(track_ids, [1234567001, 1234567002, 1234567003]);
+    }
+
+    #[test]
+    fn resolve_other() {
+        let resource: Resource = fixture("resolve_track.json");
+
+        assert!(matches!(resource, Resource::Other));
+    }
+
+    #[test]
+    fn tracks_response() {
+        let tracks_res: TracksResponse = fixture("user_tracks.json");
+
+        assert_eq!(tracks_res.collection.len(), 2);
+        assert_eq!(tracks_res.collection[0].title, "Warehouse Techno Mix");
+        assert_eq!(tracks_res.collection[0].full_duration, 5400500);
+        assert!(tracks_res
+            .next_href
+            .is_some_and(|next_href| next_href.contains("offset=")));
+    }
+
+    #[test]
+    fn tracks() {
+        let tracks: Vec<Track> = fixture("tracks.json");
+        let playable = tracks
+            .iter()
+            .filter(|track| track.is_playable())
+            .map(|track| track.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(playable, [1234567001, 1234567002]);
+    }
+
+    #[test]
+    fn track_to_item() {
+        let tracks: Vec<Track> = fixture("tracks.json");
+        let item = Item::from(tracks[1].clone());
+
+        assert_eq!(item.guid, "1234567001");
+        assert_eq!(
+            item.enclosure.file,
+            Path::new("myfavouritedj/sunset-session-1.mp3")
+        );
+        assert_eq!(item.duration, Some(3600));
+        assert_eq!(item.keywords, ["deephouse", "sunset session", "amsterdam"]);
+        assert_eq!(
+            item.categories.get("Deep House").map(Url::as_str),
+            Some("https://soundcloud.com/tags/Deep%20House")
+        );
+        assert_eq!(
+            item.image.as_ref().map(Url::as_str),
+            Some("https://i1.sndcdn.com/artworks-000111-aaaaaa-t500x500.jpg")
+        );
+    }
+
+    #[test]
+    fn track_tags() {
+        let mut track = fixture::<Vec<Track>>("tracks.json").remove(0);
+
+        track.tag_list = String::from(r#"house "deep house"  techno "" "#);
+        assert_eq!(track.tags(), ["house", "deep house", "techno"]);
+
+        track.tag_list = String::new();
+        assert!(track.tags().is_empty());
+    }
+
+    #[test]
+    fn find_client_id_in_script() {
+        let script = read_fixture("script.js");
+        let other_script = read_fixture("script_without_client_id.js");
+
+        assert_eq!(
+            find_client_id(&script).as_deref(),
+            Some("a1B2c3D4e5F6g7H8i9J0k1L2m3N4o5P6")
+        );
+        assert_eq!(find_client_id(&other_script), None);
+        assert_eq!(
+            find_client_id(r#"e.client_id="0123456789abcdef0123456789ABCDEF""#).as_deref(),
+            Some("0123456789abcdef0123456789ABCDEF")
+        );
+    }
+
+    #[test]
+    fn paging_query() {
+        let mut url = Url::parse(
+            "https://api-v2.soundcloud.com/users/1/tracks?offset=abc&limit=2&client_id=x",
+        )
+        .unwrap();
+
+        set_paging_query(&mut url, 120);
+        assert_eq!(
+            url.query(),
+            Some("offset=abc&limit=50&linked_partitioning=1")
+        );
+
+        set_paging_query(&mut url, 7);
+        assert_eq!(
+            url.query(),
+            Some("offset=abc&limit=7&linked_partitioning=1")
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//! The Twitch back-end.
+//!
+//! It uses the (undocumented) GraphQL API that is also used by the Twitch website to retrieve the
+//! feed (channel) and items (past broadcasts and highlights). There are only HLS streams of the
+//! videos, so the media content is transcoded by default.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use rocket::serde::json::{json, Value};
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+use youtube_dl::YoutubeDlOutput;
+
+use super::{
+    channel_categories, mime_extension, path_segments, BackendConfig, BackendInfo, Channel,
+    Enclosure, Item, Medium, YoutubeDlOptions,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::{Error, Result};
+
+/// The URL of the Twitch GraphQL API.
+const API_URL: &str = "https://gql.twitch.tv/gql";
+
+/// The (public) client ID used by the Twitch website.
+const CLIENT_ID: &str = "kimne78kx3ncx6brgo4mv6wki5h1ko";
+
+/// The base URL of the Twitch website.
+const WEBSITE_BASE_URL: &str = "https://www.twitch.tv";
+
+/// The paths of Twitch URLs that do not refer to users.
+const RESERVED_PATHS: [&str; 4] = ["directory", "downloads", "search", "videos"];
+
+/// The file (MIME) type of the audio-only renditions, which are HLS streams.
+const DEFAULT_FILE_TYPE: &str = "application/vnd.apple.mpegurl";
+
+/// The estimated bitrate of the audio-only renditions.
+const DEFAULT_BITRATE: u64 = 160 * 1024;
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The maximum number of videos that can be retrieved at once.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// The GraphQL query for retrieving a user (channel).
+const USER_QUERY: &str = "query($login: String!) {
+  user(login: $login) {
+    login
+    displayName
+    description
+    profileImageURL(width: 300)
+  }
+}";
+
+/// The GraphQL query for retrieving the past broadcasts and highlights of a user (channel).
+const VIDEOS_QUERY: &str = "query($login: String!, $first: Int!) {
+  user(login: $login) {
+    archives: videos(first: $first, type: ARCHIVE, sort: TIME) { ...videos }
+    highlights: videos(first: $first, type: HIGHLIGHT, sort: TIME) { ...videos }
+  }
+}
+
+fragment videos on VideoConnection {
+  edges {
+    node {
+      id
+      title
+      description
+      lengthSeconds
+      publishedAt
+      previewThumbnailURL(width: 640, height: 360)
+      game { displayName }
+    }
+  }
+}";
+
+/// The information of the Twitch back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "twitch",
+    name: "Twitch",
+    description: "Twitch login name",
+    url_patterns: &["https://www.twitch.tv/*"],
+};
+
+/// Creates a Twitch back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        api_url: settings.base_url_or("api", API_URL),
+        website_base_url: settings.base_url_or("website", WEBSITE_BASE_URL),
+        youtube_dl: settings.youtube_dl_options(),
+        cache: cache.scoped("twitch", settings.ttl),
+    }
+}
+
+/// The Twitch back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The URL of the Twitch GraphQL API.
+    api_url: Url,
+
+    /// The base URL of the Twitch website (for downloading videos).
+    website_base_url: Url,
+
+    /// The options used for running `yt-dlp`.
+    youtube_dl: YoutubeDlOptions,
+
+    /// The cache store used for users, videos and redirect URLs.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "Twitch"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For Twitch a channel ID is the login name of the user.
+        let login = channel_id.to_lowercase();
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT).min(MAX_PAGE_SIZE);
+
+        println!("⏬ Retrieving user {login} from {}...", self.api_url);
+        let user_res: UserResponse = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                &login,
+                query(
+                    self.client.clone(),
+                    self.api_url.clone(),
+                    USER_QUERY,
+                    json!({ "login": login }),
+                ),
+            )
+            .await?;
+        let user = user_res
+            .data
+            .user
+            .ok_or_else(|| Error::UnsupportedChannel(channel_id.to_string()))?;
+
+        println!(
+            "⏬ Retrieving videos of user {login} from {}...",
+            self.api_url
+        );
+        let videos_res: VideosResponse = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Items,
+                &format!("{login}/{limit}"),
+                query(
+                    self.client.clone(),
+                    self.api_url.clone(),
+                    VIDEOS_QUERY,
+                    json!({ "login": login, "first": limit }),
+                ),
+            )
+            .await?;
+        let mut videos = videos_res
+            .data
+            .user
+            .map(|videos| {
+                videos
+                    .archives
+                    .edges
+                    .into_iter()
+                    .chain(videos.highlights.edges)
+                    .map(|edge| edge.node)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        videos.sort_by_key(|video| Reverse(video.published_at));
+        videos.truncate(limit);
+
+        Ok(Channel::from(UserWithVideos(user, videos)))
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let id_part = file.with_extension("");
+        let video_id = id_part.to_string_lossy();
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                &video_id,
+                retrieve_redirect_url(
+                    self.youtube_dl.clone(),
+                    self.website_base_url.clone(),
+                    video_id.to_string(),
+                ),
+            )
+            .await
+    }
+
+    fn requires_transcoding(&self) -> bool {
+        // The audio-only renditions are HLS streams, which most podcast clients cannot play.
+        true
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // The first path segment of a channel URL is the login name of the user.
+        let login = match path_segments(url)[..] {
+            [login, ..] if !RESERVED_PATHS.contains(&login) => Some(login.to_lowercase()),
+            _ => None,
+        };
+
+        Ok(login)
+    }
+}
+
+/// A Twitch user with its videos.
+pub(crate) struct UserWithVideos(User, Vec<Video>);
+
+/// A Twitch GraphQL response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Response<T> {
+    /// The data of the response.
+    data: T,
+}
+
+/// The Twitch user response.
+type UserResponse = Response<UserData>;
+
+/// The Twitch user response data.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct UserData {
+    /// The user (if found).
+    user: Option<User>,
+}
+
+/// A Twitch user.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct User {
+    /// The login name of the user.
+    pub(crate) login: String,
+
+    /// The display name of the user.
+    pub(crate) display_name: String,
+
+    /// The description of the user.
+    pub(crate) description: Option<String>,
+
+    /// The URL of the profile image of the user.
+    #[serde(rename = "profileImageURL")]
+    pub(crate) profile_image_url: Option<Url>,
+}
+
+/// The Twitch videos response.
+type VideosResponse = Response<VideosData>;
+
+/// The Twitch videos response data.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VideosData {
+    /// The videos of the user (if found).
+    user: Option<UserVideos>,
+}
+
+/// The videos of a Twitch user.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct UserVideos {
+    /// The past broadcasts of the user.
+    archives: VideoConnection,
+
+    /// The highlights of the user.
+    highlights: VideoConnection,
+}
+
+/// A Twitch video connection (page of videos).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VideoConnection {
+    /// The edges to the videos.
+    edges: Vec<VideoEdge>,
+}
+
+/// A Twitch video edge.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VideoEdge {
+    /// The video.
+    node: Video,
+}
+
+/// A Twitch video (past broadcast or highlight).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Video {
+    /// The ID of the video.
+    pub(crate) id: String,
+
+    /// The title of the video.
+    pub(crate) title: Option<String>,
+
+    /// The description of the video.
+    pub(crate) description: Option<String>,
+
+    /// The length of the video (in seconds).
+    pub(crate) length_seconds: u32,
+
+    /// The time the video was published.
+    pub(crate) published_at: DateTime<Utc>,
+
+    /// The URL of the thumbnail of the video.
+    #[serde(rename = "previewThumbnailURL")]
+    pub(crate) preview_thumbnail_url: Option<Url>,
+
+    /// The game (category) of the video.
+    pub(crate) game: Option<Game>,
+}
+
+/// A Twitch game (category).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) struct Game {
+    /// The display name of the game.
+    pub(crate) display_name: String,
+}
+
+impl From<UserWithVideos> for Channel {
+    fn from(UserWithVideos(user, videos): UserWithVideos) -> Self {
+        let mut link = Url::parse(WEBSITE_BASE_URL).expect("URL can always be parsed");
+        link.set_path(&user.login);
+        let items = videos.into_iter().map(Item::from).collect::<Vec<_>>();
+        let games = items
+            .iter()
+            .flat_map(|item| item.categories.keys().cloned());
+        let categories = channel_categories(games);
+
+        Channel {
+            title: format!("{0} (via Twitch)", user.display_name),
+            link,
+            description: user.description.unwrap_or_default(),
+            author: Some(user.display_name),
+            categories,
+            image: user.profile_image_url,
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl From<Video> for Item {
+    fn from(video: Video) -> Self {
+        let mut file = PathBuf::from(&video.id);
+        let extension = mime_extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
+        file.set_extension(extension);
+
+        let mut link = Url::parse(WEBSITE_BASE_URL).expect("URL can always be parsed");
+        link.set_path(&format!("videos/{}", video.id));
+        let description = video
+            .description
+            .filter(|description| !description.is_empty())
+            .map(|description| format!("{description}\n\nTaken from Twitch: {link}"))
+            .unwrap_or_else(|| format!("Taken from Twitch: {link}"));
+        let categories = video
+            .game
+            .into_iter()
+            .map(|game| {
+                let mut url = Url::parse(WEBSITE_BASE_URL).expect("URL can always be parsed");
+                url.path_segments_mut().expect("URL can be a base").extend([
+                    "directory",
+                    "category",
+                    &game.display_name,
+                ]);
+
+                (game.display_name, url)
+            })
+            .collect();
+        let enclosure = Enclosure {
+            file,
+            mime_type: String::from(DEFAULT_FILE_TYPE),
+            length: estimated_file_size(video.length_seconds),
+        };
+
+        Item {
+            title: video.title.unwrap_or_else(|| video.id.clone()),
+            link,
+            description: Some(description),
+            categories,
+            enclosure,
+            duration: Some(video.length_seconds),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            guid: video.id,
+            keywords: Vec::new(),
+            season: None,
+            episode: None,
+            image: video.preview_thumbnail_url,
+            published_at: video.published_at,
+            // There is no updated at timestamp available, really.
+            updated_at: video.published_at,
+        }
+    }
+}
+
+/// Returns the estimated file size in bytes for a given duration.
+///
+/// It uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
+fn estimated_file_size(duration: u32) -> u64 {
+    DEFAULT_BITRATE * duration as u64 / 8
+}
+
+/// Runs the GraphQL query with the variables using the Twitch API at the provided URL.
+async fn query<T: DeserializeOwned>(
+    client: reqwest::Client,
+    api_url: Url,
+    query: &'static str,
+    variables: Value,
+) -> Result<T> {
+    let response = client
+        .post(api_url)
+        .header("Client-ID", CLIENT_ID)
+        .json(&json!({ "query": query, "variables": variables }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let result = response.json().await?;
+
+    Ok(result)
+}
+
+/// Retrieves the redirect URL for the provided Twitch video ID.
+///
+/// This is the URL of the audio-only rendition of the video, which is an HLS stream.
+async fn retrieve_redirect_url(
+    youtube_dl: YoutubeDlOptions,
+    website_base_url: Url,
+    video_id: String,
+) -> Result<String> {
+    let mut url = website_base_url;
+    url.set_path(&format!("videos/{video_id}"));
+
+    println!("🌍 Determining direct URL for {video_id}...");
+    let output = youtube_dl
+        .command(url)
+        .format("audio_only/worstaudio/worst")
+        .run_async()
+        .await?;
+
+    match output {
+        YoutubeDlOutput::SingleVideo(yt_item) => yt_item.url.ok_or(Error::NoRedirectUrlFound),
+        _ => Err(Error::NoRedirectUrlFound),
+    }
+}
@@ -0,0 +1,475 @@
+//! The Vimeo back-end.
+//!
+//! It uses the Vimeo API to retrieve the feed (user, channel or showcase) and items (videos),
+//! using the anonymous JSON web token that is also used by the Vimeo website.
+//! See also: <https://developer.vimeo.com/api/reference>
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
+use youtube_dl::YoutubeDlOutput;
+
+use super::{
+    channel_categories, mime_extension, path_segments, BackendConfig, BackendInfo, Channel,
+    Enclosure, Item, Medium, YoutubeDlOptions,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::{Error, Result};
+
+/// The base URL for the Vimeo API.
+const API_BASE_URL: &str = "https://api.vimeo.com";
+
+/// The base URL of the Vimeo website.
+const WEBSITE_BASE_URL: &str = "https://vimeo.com";
+
+/// The path (on the website) for retrieving an anonymous JSON web token for the API.
+const JWT_PATH: &str = "_next/jwt";
+
+/// The fields of videos that are retrieved.
+const VIDEO_FIELDS: &str = "uri,name,description,link,duration,created_time,release_time,\
+                            modified_time,pictures.sizes,tags.name,tags.canonical";
+
+/// The fields of users, channels and showcases that are retrieved.
+const CONTAINER_FIELDS: &str = "name,bio,description,link,pictures.sizes,user.name";
+
+/// The estimated bitrate of the lowest-bitrate progressive files.
+const DEFAULT_BITRATE: u64 = 512 * 1024;
+
+/// The default file (MIME) type used by Vimeo for progressive files.
+const DEFAULT_FILE_TYPE: &str = "video/mp4";
+
+/// The default page size.
+const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// The information of the Vimeo back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "vimeo",
+    name: "Vimeo",
+    description:
+        "Vimeo username, or channel or showcase path with %2F for slashes, e.g. showcase%2F1234567",
+    url_patterns: &["https://vimeo.com/*"],
+};
+
+/// Creates a Vimeo back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        client: settings.http_client(),
+        api_base_url: settings.base_url_or("api", API_BASE_URL),
+        website_base_url: settings.base_url_or("website", WEBSITE_BASE_URL),
+        youtube_dl: settings.youtube_dl_options(),
+        cache: cache.scoped("vimeo", settings.ttl),
+    }
+}
+
+/// The Vimeo back-end.
+pub struct Backend {
+    /// The HTTP client used for requests to the upstream service.
+    client: reqwest::Client,
+
+    /// The base URL for the Vimeo API.
+    api_base_url: Url,
+
+    /// The base URL of the Vimeo website (for the JSON web token and downloading files).
+    website_base_url: Url,
+
+    /// The options used for running `yt-dlp`.
+    youtube_dl: YoutubeDlOptions,
+
+    /// The cache store used for users, channels, showcases, videos and redirect URLs.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "Vimeo"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For Vimeo a channel ID is the path of the URL of a user (`user`), a channel
+        // (`channels/name`) or a showcase (`showcase/id`).
+        let (api_path, sorted) = match channel_id.split('/').collect::<Vec<_>>()[..] {
+            [user] if is_valid_name(user) => (format!("users/{user}"), true),
+            ["channels", name] if is_valid_name(name) => (format!("channels/{name}"), true),
+            ["showcase", id] if is_valid_name(id) => (format!("albums/{id}"), false),
+            _ => return Err(Error::UnsupportedChannel(channel_id.to_string())),
+        };
+
+        let jwt_url = self.website_base_url.join(JWT_PATH)?;
+        let mut container_url = self.api_base_url.clone();
+        container_url.set_path(&api_path);
+        container_url
+            .query_pairs_mut()
+            .append_pair("fields", CONTAINER_FIELDS);
+        println!("⏬ Retrieving {channel_id} from {container_url}...");
+        let container = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                container_url.as_str(),
+                fetch(self.client.clone(), jwt_url.clone(), container_url.clone()),
+            )
+            .await?;
+
+        // The items of a channel are its videos; showcases keep their own order.
+        let limit = item_limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let page_size = limit.min(DEFAULT_PAGE_SIZE);
+        let mut videos_url = self.api_base_url.clone();
+        videos_url.set_path(&format!("{api_path}/videos"));
+        println!("⏬ Retrieving videos of {channel_id} from {videos_url}...");
+        let mut page = 1;
+        let mut videos = Vec::with_capacity(page_size);
+        loop {
+            set_paging_query(&mut videos_url, page_size, page, sorted);
+            let videos_res: VideosResponse = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    videos_url.as_str(),
+                    fetch(self.client.clone(), jwt_url.clone(), videos_url.clone()),
+                )
+                .await?;
+            let count = videos_res.data.len();
+            videos.extend(videos_res.data);
+
+            // Continue onto the next page, if there is one and the limit was not reached.
+            page += 1;
+            let has_next = videos_res.paging.and_then(|paging| paging.next).is_some();
+            if videos.len() >= limit || count == 0 || !has_next {
+                break;
+            }
+        }
+        videos.truncate(limit);
+
+        Ok(Channel::from(ContainerWithVideos(container, videos)))
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        let id_part = file.with_extension("");
+        let video_id = id_part.to_string_lossy();
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                &video_id,
+                retrieve_redirect_url(
+                    self.youtube_dl.clone(),
+                    self.website_base_url.clone(),
+                    video_id.to_string(),
+                ),
+            )
+            .await
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // The path of a user, channel or showcase URL is the channel ID. Numeric paths refer to
+        // videos.
+        let channel_id = match path_segments(url)[..] {
+            ["channels", name, ..] if is_valid_name(name) => Some(format!("channels/{name}")),
+            ["showcase", id, ..] if is_valid_name(id) => Some(format!("showcase/{id}")),
+            [user] if is_valid_name(user) && !user.chars().all(|c| c.is_ascii_digit()) => {
+                Some(String::from(user))
+            }
+            _ => None,
+        };
+
+        Ok(channel_id)
+    }
+}
+
+/// A Vimeo user, channel or showcase with its videos.
+pub(crate) struct ContainerWithVideos(Container, Vec<Video>);
+
+/// A Vimeo user, channel or showcase (response).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Container {
+    /// The name of the user, channel or showcase.
+    pub(crate) name: String,
+
+    /// The description (or bio of the user).
+    #[serde(alias = "bio")]
+    pub(crate) description: Option<String>,
+
+    /// The original URL of the user, channel or showcase.
+    pub(crate) link: Url,
+
+    /// The pictures of the user, channel or showcase.
+    pub(crate) pictures: Option<Pictures>,
+
+    /// The user that owns the channel or showcase.
+    pub(crate) user: Option<Owner>,
+}
+
+/// The owner of a Vimeo channel or showcase.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Owner {
+    /// The name of the owner.
+    pub(crate) name: String,
+}
+
+/// A collection of different sizes of a Vimeo picture.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Pictures {
+    /// The sizes of the picture.
+    #[serde(default)]
+    pub(crate) sizes: Vec<PictureSize>,
+}
+
+impl Pictures {
+    /// Returns the URL of the largest size of the picture (if any).
+    fn largest(&self) -> Option<Url> {
+        self.sizes
+            .iter()
+            .max_by_key(|size| size.width)
+            .map(|size| size.link.clone())
+    }
+}
+
+/// A size of a Vimeo picture.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct PictureSize {
+    /// The width of the picture.
+    pub(crate) width: u32,
+
+    /// The URL of the picture.
+    pub(crate) link: Url,
+}
+
+/// The Vimeo videos response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VideosResponse {
+    /// The videos of the current page.
+    data: Vec<Video>,
+
+    /// The paging information (if any).
+    paging: Option<Paging>,
+}
+
+/// The Vimeo paging information.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Paging {
+    /// The API path of the next page.
+    next: Option<String>,
+}
+
+/// A Vimeo video.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Video {
+    /// The API URI of the video (`/videos/<id>`).
+    pub(crate) uri: String,
+
+    /// The name of the video.
+    pub(crate) name: String,
+
+    /// The description of the video.
+    pub(crate) description: Option<String>,
+
+    /// The original URL of the video.
+    pub(crate) link: Url,
+
+    /// The duration of the video (in seconds).
+    pub(crate) duration: u32,
+
+    /// The time the video was created.
+    pub(crate) created_time: DateTime<Utc>,
+
+    /// The time the video was released (if different from created).
+    pub(crate) release_time: Option<DateTime<Utc>>,
+
+    /// The time the video was last modified.
+    pub(crate) modified_time: Option<DateTime<Utc>>,
+
+    /// The pictures (thumbnails) of the video.
+    pub(crate) pictures: Option<Pictures>,
+
+    /// The tags of the video.
+    #[serde(default)]
+    pub(crate) tags: Vec<Tag>,
+}
+
+/// A Vimeo video tag.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Tag {
+    /// The name of the tag.
+    pub(crate) name: String,
+
+    /// The canonical name of the tag (used in its URL).
+    pub(crate) canonical: String,
+}
+
+impl From<ContainerWithVideos> for Channel {
+    fn from(ContainerWithVideos(container, videos): ContainerWithVideos) -> Self {
+        let author = container
+            .user
+            .map(|owner| owner.name)
+            .unwrap_or_else(|| container.name.clone());
+        let items = videos.into_iter().map(Item::from).collect::<Vec<_>>();
+        // The keywords of the items are the names of the tags of the videos.
+        let tags = items.iter().flat_map(|item| item.keywords.clone());
+        let categories = channel_categories(tags);
+
+        Channel {
+            title: format!("{0} (via Vimeo)", container.name),
+            link: container.link,
+            description: container.description.unwrap_or_default(),
+            author: Some(author),
+            categories,
+            image: container.pictures.as_ref().and_then(Pictures::largest),
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl From<Video> for Item {
+    fn from(video: Video) -> Self {
+        let id = video.uri.trim_start_matches("/videos/").to_owned();
+        let mut file = PathBuf::from(&id);
+        let extension = mime_extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
+        file.set_extension(extension);
+
+        let description = video
+            .description
+            .filter(|description| !description.is_empty())
+            .map(|description| format!("{description}\n\nTaken from Vimeo: {}", video.link))
+            .unwrap_or_else(|| format!("Taken from Vimeo: {}", video.link));
+        let categories = video
+            .tags
+            .iter()
+            .map(|tag| {
+                let mut url = Url::parse(WEBSITE_BASE_URL).expect("URL can always be parsed");
+                url.path_segments_mut()
+                    .expect("URL can be a base")
+                    .extend(["tag", &tag.canonical]);
+
+                (tag.name.clone(), url)
+            })
+            .collect();
+        let keywords = video.tags.into_iter().map(|tag| tag.name).collect();
+        let enclosure = Enclosure {
+            file,
+            mime_type: String::from(DEFAULT_FILE_TYPE),
+            length: estimated_file_size(video.duration),
+        };
+        let published_at = video.release_time.unwrap_or(video.created_time);
+
+        Item {
+            title: video.name,
+            link: video.link,
+            description: Some(description),
+            categories,
+            enclosure,
+            duration: Some(video.duration),
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            guid: id,
+            keywords,
+            season: None,
+            episode: None,
+            image: video.pictures.as_ref().and_then(Pictures::largest),
+            published_at,
+            updated_at: video.modified_time.unwrap_or(published_at),
+        }
+    }
+}
+
+/// Returns whether the provided name (or ID) of a user, channel or showcase is valid.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Returns the estimated file size in bytes for a given duration.
+///
+/// It uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
+fn estimated_file_size(duration: u32) -> u64 {
+    DEFAULT_BITRATE * duration as u64 / 8
+}
+
+/// The anonymous JSON web token response.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct JwtResponse {
+    /// The JSON web token.
+    token: String,
+}
+
+/// Fetches a response from the Vimeo API URL.
+///
+/// An anonymous JSON web token is retrieved from the JSON web token URL first, since it expires
+/// quickly.
+async fn fetch<T: DeserializeOwned>(client: reqwest::Client, jwt_url: Url, url: Url) -> Result<T> {
+    let jwt_res: JwtResponse = client
+        .get(jwt_url)
+        .header("X-Requested-With", "XMLHttpRequest")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let response = client
+        .get(url)
+        .header("Authorization", format!("jwt {}", jwt_res.token))
+        .send()
+        .await?
+        .error_for_status()?;
+    let result = response.json().await?;
+
+    Ok(result)
+}
+
+/// Set paging (and sorting) query pairs for URL.
+///
+/// The page size should be the same for all pages, since the pages are numbered.
+fn set_paging_query(url: &mut Url, page_size: usize, page: usize, sorted: bool) {
+    let mut query_pairs = url.query_pairs_mut();
+    query_pairs
+        .clear()
+        .append_pair("fields", VIDEO_FIELDS)
+        .append_pair("per_page", &format!("{page_size}"))
+        .append_pair("page", &format!("{page}"));
+    if sorted {
+        query_pairs
+            .append_pair("sort", "date")
+            .append_pair("direction", "desc");
+    }
+}
+
+/// Retrieves the redirect URL for the provided Vimeo video ID.
+async fn retrieve_redirect_url(
+    youtube_dl: YoutubeDlOptions,
+    website_base_url: Url,
+    video_id: String,
+) -> Result<String> {
+    let mut url = website_base_url;
+    url.set_path(&video_id);
+
+    println!("🌍 Determining direct URL for {video_id}...");
+    // Select a progressive audio-only file if available, otherwise the progressive (so no HLS or
+    // DASH) video file with the lowest bitrate.
+    let output = youtube_dl
+        .command(url)
+        .format("bestaudio[protocol^=http]/worst[protocol^=http][acodec!=none]")
+        .run_async()
+        .await?;
+
+    match output {
+        YoutubeDlOutput::SingleVideo(yt_item) => yt_item.url.ok_or(Error::NoRedirectUrlFound),
+        _ => Err(Error::NoRedirectUrlFound),
+    }
+}
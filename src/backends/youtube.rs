@@ -1,52 +1,242 @@
 //! The YouTube back-end.
 //!
 //! It uses the `ytextract` crate to retrieve the feed (channel or playlist) and items (videos).
+//! If extracting fails and the Invidious back-end is enabled with a configured instance, it falls
+//! back to the Invidious back-end (see [`super::invidious`]). If enabled, the caption tracks of
+//! the videos are retrieved using `yt-dlp` to provide transcripts.
 
 use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
-use cached::macros::cached;
 use chrono::{TimeZone, Utc};
 use reqwest::Url;
 use rocket::futures::StreamExt;
+use tokio::sync::Semaphore;
+use youtube_dl::{Subtitle, YoutubeDlOutput};
 use ytextract::playlist::video::{Error as YouTubeVideoError, Video as YouTubePlaylistVideo};
 use ytextract::{
     Channel as YouTubeChannel, Client, Playlist as YouTubePlaylist, Stream as YouTubeStream,
     Video as YouTubeVideo,
 };
 
-use super::{Channel, Enclosure, Item};
+use super::{
+    invidious, mime_extension, path_segments, BackendConfig, BackendInfo, Channel, Enclosure, Item,
+    Medium, Transcript, YoutubeDlOptions,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::chapters::parse_chapters;
 use crate::{Error, Result};
 
 /// The base URL for YouTube channels.
-const CHANNEL_BASE_URL: &str = "https://www.youtube.com/channel";
+pub(crate) const CHANNEL_BASE_URL: &str = "https://www.youtube.com/channel";
 
 /// The default item limit.
 const DEFAULT_ITEM_LIMIT: usize = 50;
 
 /// The base URL for YouTube playlists.
-const PLAYLIST_BASE_URL: &str = "https://www.youtube.com/channel";
+pub(crate) const PLAYLIST_BASE_URL: &str = "https://www.youtube.com/channel";
+
+/// The prefixes of the IDs of the playlists that can be retrieved.
+///
+/// These are user playlists (`PL`), channel uploads (`UU`), favorites (`FL`), albums (`OLAK`) and
+/// curated music playlists (`RDCLAK`). Other playlists, like the watch later (`WL`) and liked
+/// videos (`LL`) lists or mixes (`RD`), are private or generated per viewer.
+const PLAYLIST_ID_PREFIXES: [&str; 5] = ["PL", "UU", "FL", "OLAK", "RDCLAK"];
 
 /// The base URL for YouTube videos.
-const VIDEO_BASE_URL: &str = "https://www.youtube.com/watch";
+pub(crate) const VIDEO_BASE_URL: &str = "https://www.youtube.com/watch";
+
+/// The maximum number of caption tracks that are retrieved concurrently in the background.
+const MAX_BACKGROUND_CAPTION_FETCHES: usize = 2;
+
+/// The permits for retrieving caption tracks in the background.
+static BACKGROUND_CAPTION_PERMITS: Semaphore = Semaphore::const_new(MAX_BACKGROUND_CAPTION_FETCHES);
+
+/// The information of the YouTube back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "youtube",
+    name: "YouTube",
+    description: "YouTube channel or playlist ID",
+    url_patterns: &[
+        "https://www.youtube.com/*",
+        "https://youtube.com/*",
+        "https://m.youtube.com/*",
+        "https://music.youtube.com/*",
+    ],
+};
 
 /// Creates a YouTube back-end.
-pub(crate) fn backend() -> Backend {
-    Backend::new()
+///
+/// If the Invidious back-end is enabled and has a configured instance, it is used as fallback.
+pub(crate) fn backend(
+    cache: &Cache,
+    settings: &BackendConfig,
+    invidious_settings: &BackendConfig,
+) -> Backend {
+    let fallback = (invidious_settings.enabled
+        && invidious_settings.base_urls.contains_key("instance"))
+    .then(|| invidious::backend(cache, invidious_settings));
+
+    Backend::new(
+        cache.scoped("youtube", settings.ttl),
+        settings.http_client(),
+        settings.youtube_dl_options(),
+        settings.transcripts,
+        fallback,
+    )
 }
 
 /// The YouTube back-end.
 pub struct Backend {
     /// The client capable of interacting with YouTube.
     client: Client,
+
+    /// The HTTP client used for requests to the YouTube website.
+    http_client: reqwest::Client,
+
+    /// The options used for running `yt-dlp` (to retrieve caption tracks).
+    youtube_dl: YoutubeDlOptions,
+
+    /// Whether transcripts are provided for the videos.
+    transcripts: bool,
+
+    /// The cache store used for channels, playlists, videos and redirect URLs.
+    cache: Cache,
+
+    /// The Invidious back-end to fall back to if extracting fails (if any).
+    fallback: Option<invidious::Backend>,
 }
 
 impl Backend {
     /// Creates a new YouTube back-end.
-    fn new() -> Self {
+    fn new(
+        cache: Cache,
+        http_client: reqwest::Client,
+        youtube_dl: YoutubeDlOptions,
+        transcripts: bool,
+        fallback: Option<invidious::Backend>,
+    ) -> Self {
         let client = Client::new();
 
-        Self { client }
+        Self {
+            client,
+            http_client,
+            youtube_dl,
+            transcripts,
+            cache,
+            fallback,
+        }
+    }
+
+    /// Returns the channel with its currently contained content items using `ytextract`.
+    async fn extract_channel(
+        &self,
+        channel_id: &str,
+        item_limit: Option<usize>,
+    ) -> Result<Channel> {
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+        let items_key = format!("{channel_id}/{limit}");
+
+        if is_playlist_id(channel_id) {
+            let mut channel = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Channel,
+                    channel_id,
+                    fetch_playlist(self.client.clone(), channel_id.to_owned()),
+                )
+                .await?;
+            channel.items = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    &items_key,
+                    fetch_playlist_videos(self.client.clone(), channel_id.to_owned(), limit),
+                )
+                .await?;
+            self.add_transcripts(&mut channel)?;
+
+            Ok(channel)
+        } else {
+            let mut channel = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Channel,
+                    channel_id,
+                    fetch_channel(self.client.clone(), channel_id.to_owned()),
+                )
+                .await?;
+            channel.items = self
+                .cache
+                .get_or_fetch(
+                    CacheKind::Items,
+                    &items_key,
+                    fetch_channel_videos(self.client.clone(), channel_id.to_owned(), limit),
+                )
+                .await?;
+            self.add_transcripts(&mut channel)?;
+
+            Ok(channel)
+        }
+    }
+
+    /// Adds the transcripts to the items of the channel (if enabled).
+    ///
+    /// The transcripts are only added if the caption languages of the video have been retrieved
+    /// already; they are retrieved in the background otherwise so they can be used later.
+    fn add_transcripts(&self, channel: &mut Channel) -> Result<()> {
+        if !self.transcripts {
+            return Ok(());
+        }
+
+        for item in &mut channel.items {
+            // The GUID of an item is the video ID.
+            let key = caption_languages_key(&item.guid);
+            match self.cache.peek::<Vec<String>>(CacheKind::Items, &key)? {
+                Some(languages) => {
+                    item.transcripts = languages
+                        .into_iter()
+                        .map(|language| video_transcript(&item.guid, language))
+                        .collect();
+                }
+                None => self.cache.prefetch(
+                    CacheKind::Items,
+                    &key,
+                    fetch_caption_languages_in_background(
+                        self.youtube_dl.clone(),
+                        item.guid.clone(),
+                    ),
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the channel ID of the channel at the provided (handle or vanity) URL.
+    async fn resolve_channel_id(&self, url: &Url) -> Result<String> {
+        println!("🌍 Determining channel ID for {url}...");
+        self.cache
+            .get_or_fetch(
+                CacheKind::Channel,
+                url.as_str(),
+                fetch_channel_id(self.http_client.clone(), url.clone()),
+            )
+            .await
+    }
+
+    /// Returns the redirect URL for the provided download file path using `ytextract`.
+    async fn extract_redirect_url(&self, file: &Path) -> Result<String> {
+        let id_part = file.with_extension("");
+        let video_id = id_part.to_string_lossy();
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                &video_id,
+                retrieve_redirect_url(self.client.clone(), video_id.to_string()),
+            )
+            .await
     }
 }
 
@@ -57,45 +247,125 @@ impl super::Backend for Backend {
     }
 
     async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
-        // We assume it is a YouTube playlist ID if the channel ID starts with
-        // "PL"/"OLAK"/"RDCLAK"; it is considered to be a YouTube channel ID otherwise.
-        if channel_id.starts_with("PL")
-            || channel_id.starts_with("OLAK")
-            || channel_id.starts_with("RDCLAK")
-        {
-            let (yt_playlist, yt_videos_w_streams) =
-                fetch_playlist_videos(&self.client, channel_id, item_limit).await?;
+        match (
+            self.extract_channel(channel_id, item_limit).await,
+            &self.fallback,
+        ) {
+            (Err(err), Some(fallback)) if is_extract_error(&err) => {
+                eprintln!("⚠️ Extracting channel {channel_id} failed: {err}, using Invidious...");
+                fallback.channel(channel_id, item_limit).await
+            }
+            (result, _) => result,
+        }
+    }
 
-            Ok(Channel::from(YouTubePlaylistWithVideos(
-                yt_playlist,
-                yt_videos_w_streams,
-            )))
-        } else {
-            let (yt_channel, yt_videos_w_streams) =
-                fetch_channel_videos(&self.client, channel_id, item_limit).await?;
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        match (self.extract_redirect_url(file).await, &self.fallback) {
+            (Err(err), Some(fallback))
+                if is_extract_error(&err) || matches!(err, Error::NoRedirectUrlFound) =>
+            {
+                eprintln!("⚠️ Extracting redirect URL failed: {err}, using Invidious...");
+                fallback.redirect_url(file).await
+            }
+            (result, _) => result,
+        }
+    }
 
-            Ok(Channel::from(YouTubeChannelWithVideos(
-                yt_channel,
-                yt_videos_w_streams,
-            )))
+    async fn resolve(&self, url: &Url) -> Result<Option<String>> {
+        // A playlist ID in the query (also of video URLs) is the channel ID, if the playlist can
+        // be retrieved, or the channel ID in the path. Handles and vanity URLs need to be resolved
+        // to the channel ID.
+        if let Some((_, playlist_id)) = url
+            .query_pairs()
+            .find(|(key, value)| key == "list" && is_playlist_id(value))
+        {
+            return Ok(Some(playlist_id.into_owned()));
+        }
+        match path_segments(url)[..] {
+            ["channel", channel_id, ..] => Ok(Some(String::from(channel_id))),
+            [handle, ..] if handle.starts_with('@') => self.resolve_channel_id(url).await.map(Some),
+            ["c" | "user", _, ..] => self.resolve_channel_id(url).await.map(Some),
+            _ => Ok(None),
         }
     }
 
-    async fn redirect_url(&self, file: &Path) -> Result<String> {
-        let id_part = file.with_extension("");
-        let video_id = id_part.to_string_lossy();
+    async fn transcript(&self, file: &Path) -> Result<Option<String>> {
+        // The transcript file path is `<video ID>.<language>.vtt`.
+        let Some((video_id, language)) = file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split_once('.'))
+        else {
+            return Ok(None);
+        };
+        if !self.transcripts {
+            return Ok(None);
+        }
+        // Only the transcripts that are in the feeds are provided.
+        let languages = self
+            .cache
+            .peek::<Vec<String>>(CacheKind::Items, &caption_languages_key(video_id))?
+            .unwrap_or_default();
+        if !languages.iter().any(|known| known == language) {
+            return Ok(None);
+        }
 
-        retrieve_redirect_url(&self.client, &video_id).await
+        // The URLs of the caption tracks expire, so they are only determined when needed.
+        let transcript = self
+            .cache
+            .get_or_fetch(
+                CacheKind::Items,
+                &format!("transcript/{video_id}.{language}"),
+                fetch_transcript(
+                    self.youtube_dl.clone(),
+                    self.http_client.clone(),
+                    video_id.to_owned(),
+                    language.to_owned(),
+                ),
+            )
+            .await?;
+
+        Ok(Some(transcript))
     }
 }
 
-/// A YouTube playlist with its videos.
-#[derive(Clone, Debug)]
-pub(crate) struct YouTubePlaylistWithVideos(YouTubePlaylist, Vec<YouTubeVideoWithStream>);
+/// Returns whether the provided error is caused by extracting from YouTube failing.
+///
+/// This includes the case where videos were found, but none of their streams could be extracted.
+fn is_extract_error(err: &Error) -> bool {
+    matches!(err, Error::NoStreamsFound | Error::YtExtract(_))
+}
+
+/// Returns whether the provided ID is a YouTube playlist ID.
+///
+/// We assume it is a YouTube playlist ID if the ID starts with one of the prefixes of public
+/// playlists that can be retrieved (see [`PLAYLIST_ID_PREFIXES`]); it is considered to be a
+/// YouTube channel ID otherwise.
+pub(crate) fn is_playlist_id(id: &str) -> bool {
+    PLAYLIST_ID_PREFIXES
+        .iter()
+        .any(|prefix| id.starts_with(prefix))
+}
 
-/// A YouTube channel with its videos.
+/// A caption track of a YouTube video.
 #[derive(Clone, Debug)]
-pub(crate) struct YouTubeChannelWithVideos(YouTubeChannel, Vec<YouTubeVideoWithStream>);
+struct CaptionTrack {
+    /// The language (code) of the captions.
+    language: String,
+
+    /// The URL of the captions in WebVTT format.
+    ///
+    /// This URL is signed and expires after a while, so it should not be cached.
+    url: String,
+}
+
+/// Returns the item transcript in the provided language of the video with the provided ID.
+fn video_transcript(video_id: &str, language: String) -> Transcript {
+    Transcript {
+        file: PathBuf::from(format!("{video_id}.{language}.vtt")),
+        language,
+    }
+}
 
 /// A YouTube video with its stream.
 #[derive(Clone, Debug)]
@@ -110,10 +380,8 @@ struct YouTubeVideoWithStream {
     content_length: u64,
 }
 
-impl From<YouTubeChannelWithVideos> for Channel {
-    fn from(
-        YouTubeChannelWithVideos(yt_channel, yt_videos_w_streams): YouTubeChannelWithVideos,
-    ) -> Self {
+impl From<YouTubeChannel> for Channel {
+    fn from(yt_channel: YouTubeChannel) -> Self {
         let mut link = Url::parse(CHANNEL_BASE_URL).expect("valid URL");
         let title = format!("{0} (via YouTube)", yt_channel.name());
         let description = yt_channel.description().to_string();
@@ -127,7 +395,12 @@ impl From<YouTubeChannelWithVideos> for Channel {
             .avatar()
             .max_by_key(|av| av.width * av.height)
             .map(|av| av.url.clone());
-        let items = yt_videos_w_streams.into_iter().map(Item::from).collect();
+        let images = yt_channel
+            .avatar()
+            .map(|av| (av.width as u32, av.url.clone()))
+            .collect();
+        // The items are fetched (and cached) separately.
+        let items = Vec::new();
 
         Channel {
             title,
@@ -136,15 +409,15 @@ impl From<YouTubeChannelWithVideos> for Channel {
             author,
             categories,
             image,
+            images,
+            medium: Medium::Podcast,
             items,
         }
     }
 }
 
-impl From<YouTubePlaylistWithVideos> for Channel {
-    fn from(
-        YouTubePlaylistWithVideos(yt_playlist, yt_videos_w_streams): YouTubePlaylistWithVideos,
-    ) -> Self {
+impl From<YouTubePlaylist> for Channel {
+    fn from(yt_playlist: YouTubePlaylist) -> Self {
         let title = format!("{0} (via YouTube)", yt_playlist.title());
         let mut link = Url::parse(PLAYLIST_BASE_URL).expect("valid URL");
         let description = yt_playlist.description().to_string();
@@ -158,7 +431,13 @@ impl From<YouTubePlaylistWithVideos> for Channel {
             .iter()
             .max_by_key(|tn| tn.width * tn.height)
             .map(|tn| tn.url.clone());
-        let items = yt_videos_w_streams.into_iter().map(Item::from).collect();
+        let images = yt_playlist
+            .thumbnails()
+            .iter()
+            .map(|tn| (tn.width as u32, tn.url.clone()))
+            .collect();
+        // The items are fetched (and cached) separately.
+        let items = Vec::new();
 
         Channel {
             title,
@@ -167,6 +446,8 @@ impl From<YouTubePlaylistWithVideos> for Channel {
             author,
             categories,
             image,
+            images,
+            medium: Medium::Podcast,
             items,
         }
     }
@@ -188,7 +469,7 @@ impl From<YouTubeVideoWithStream> for Item {
         if let Some(sep_idx) = mime_type.find(';') {
             mime_type.truncate(sep_idx);
         }
-        let extension = mime_db::extension(&mime_type).unwrap_or_default();
+        let extension = mime_extension(&mime_type).unwrap_or_default();
         let file = PathBuf::from(&id).with_extension(extension);
         let enclosure = Enclosure {
             file,
@@ -214,6 +495,7 @@ impl From<YouTubeVideoWithStream> for Item {
             })
             .collect();
         let duration = Some(video.duration().as_secs() as u32);
+        let chapters = parse_chapters(video_description);
         let keywords = video.keywords().clone();
         let image = video
             .thumbnails()
@@ -235,8 +517,12 @@ impl From<YouTubeVideoWithStream> for Item {
             categories,
             enclosure,
             duration,
+            chapters,
+            transcripts: Vec::new(),
             guid: id,
             keywords,
+            season: None,
+            episode: None,
             image,
             published_at,
             updated_at,
@@ -244,55 +530,75 @@ impl From<YouTubeVideoWithStream> for Item {
     }
 }
 
-/// Fetches the YouTube playlist videos for the given ID.
-///
-/// If the result is [`Ok`], the playlist will be cached for 24 hours for the given playlist ID.
-#[cached(
-    key = "(String, Option<usize>)",
-    convert = r#"{ (playlist_id.to_owned(), item_limit) }"#,
-    ttl = 86400
-)]
+/// Fetches the YouTube playlist for the given ID.
+async fn fetch_playlist(client: Client, playlist_id: String) -> Result<Channel> {
+    let id = playlist_id.parse()?;
+    let yt_playlist = client.playlist(id).await?;
+
+    Ok(Channel::from(yt_playlist))
+}
+
+/// Fetches the YouTube playlist videos for the given ID (up to the given limit).
 async fn fetch_playlist_videos(
-    client: &Client,
-    playlist_id: &str,
-    item_limit: Option<usize>,
-) -> Result<(YouTubePlaylist, Vec<YouTubeVideoWithStream>)> {
+    client: Client,
+    playlist_id: String,
+    limit: usize,
+) -> Result<Vec<Item>> {
     let id = playlist_id.parse()?;
-    let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
     let yt_playlist = client.playlist(id).await?;
-    let yt_videos_w_streams = yt_playlist
+    let mut video_count = 0;
+    let items = yt_playlist
         .videos()
+        .inspect(|_| video_count += 1)
         .filter_map(fetch_stream)
         .take(limit)
+        .map(Item::from)
         .collect()
         .await;
 
-    Ok((yt_playlist, yt_videos_w_streams))
+    check_streams_found(items, video_count)
 }
 
-/// Fetches the YouTube channel videos for the given ID.
-#[cached(
-    key = "(String, Option<usize>)",
-    convert = r#"{ (channel_id.to_owned(), item_limit) }"#,
-    ttl = 86400
-)]
+/// Fetches the YouTube channel for the given ID.
+async fn fetch_channel(client: Client, channel_id: String) -> Result<Channel> {
+    let id = channel_id.parse()?;
+    let yt_channel = client.channel(id).await?;
+
+    Ok(Channel::from(yt_channel))
+}
+
+/// Fetches the YouTube channel videos for the given ID (up to the given limit).
 async fn fetch_channel_videos(
-    client: &Client,
-    channel_id: &str,
-    item_limit: Option<usize>,
-) -> Result<(YouTubeChannel, Vec<YouTubeVideoWithStream>)> {
+    client: Client,
+    channel_id: String,
+    limit: usize,
+) -> Result<Vec<Item>> {
     let id = channel_id.parse()?;
-    let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
     let yt_channel = client.channel(id).await?;
-    let yt_videos_w_streams = yt_channel
+    let mut video_count = 0;
+    let items = yt_channel
         .uploads()
         .await?
+        .inspect(|_| video_count += 1)
         .filter_map(fetch_stream)
         .take(limit)
+        .map(Item::from)
         .collect()
         .await;
 
-    Ok((yt_channel, yt_videos_w_streams))
+    check_streams_found(items, video_count)
+}
+
+/// Checks whether items were found if there were videos.
+///
+/// If none of the streams of the videos could be extracted, extracting is considered to have
+/// failed, instead of the channel or playlist being empty.
+fn check_streams_found(items: Vec<Item>, video_count: usize) -> Result<Vec<Item>> {
+    if items.is_empty() && video_count > 0 {
+        return Err(Error::NoStreamsFound);
+    }
+
+    Ok(items)
 }
 
 /// Fetches the stream and relevant metadata for a YouTube video result.
@@ -325,11 +631,136 @@ async fn fetch_stream(
     }
 }
 
-/// Retrieves the redirect URL for the provided YouTube video ID.
+/// Fetches the channel ID from the canonical link of the channel page at the URL.
+async fn fetch_channel_id(client: reqwest::Client, url: Url) -> Result<String> {
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    let html = response.text().await?;
+    let prefix = format!(r#"<link rel="canonical" href="{CHANNEL_BASE_URL}/"#);
+
+    html.split_once(&prefix)
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .map(|(channel_id, _)| String::from(channel_id))
+        .filter(|channel_id| channel_id.starts_with("UC"))
+        .ok_or_else(|| Error::UnsupportedChannel(url.to_string()))
+}
+
+/// Returns the cache key of the caption languages of the video with the provided ID.
+fn caption_languages_key(video_id: &str) -> String {
+    format!("caption_languages/{video_id}")
+}
+
+/// Fetches the languages of the caption tracks for the provided YouTube video ID in the
+/// background.
+///
+/// The number of caption tracks that are retrieved concurrently in the background is limited (see
+/// [`MAX_BACKGROUND_CAPTION_FETCHES`]).
+async fn fetch_caption_languages_in_background(
+    youtube_dl: YoutubeDlOptions,
+    video_id: String,
+) -> Result<Vec<String>> {
+    let _permit = BACKGROUND_CAPTION_PERMITS
+        .acquire()
+        .await
+        .expect("semaphore is never closed");
+    let caption_tracks = fetch_caption_tracks(youtube_dl, video_id).await?;
+
+    Ok(caption_tracks
+        .into_iter()
+        .map(|caption_track| caption_track.language)
+        .collect())
+}
+
+/// Fetches the caption tracks for the provided YouTube video ID using `yt-dlp`.
+///
+/// All subtitles are used, but of the automatic captions only those in the original language of
+/// the video, since the others are machine translations.
+async fn fetch_caption_tracks(
+    youtube_dl: YoutubeDlOptions,
+    video_id: String,
+) -> Result<Vec<CaptionTrack>> {
+    println!("🌍 Determining caption tracks for video {video_id}...");
+    let url = format!("{VIDEO_BASE_URL}?v={video_id}");
+    let output = youtube_dl.command(url).run_async().await?;
+    let YoutubeDlOutput::SingleVideo(video) = output else {
+        return Ok(Vec::new());
+    };
+
+    let vtt_url = |subtitles: Vec<Subtitle>| {
+        subtitles
+            .into_iter()
+            .find(|subtitle| subtitle.ext.as_deref() == Some("vtt"))
+            .and_then(|subtitle| subtitle.url)
+    };
+    let mut caption_tracks = video
+        .subtitles
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(language, subtitles)| {
+            let url = vtt_url(subtitles?)?;
+
+            Some(CaptionTrack { language, url })
+        })
+        .collect::<Vec<_>>();
+    let original_language = video.language;
+    let automatic_captions = video
+        .automatic_captions
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(language, subtitles)| {
+            let language = match language.strip_suffix("-orig") {
+                Some(language) => String::from(language),
+                None if original_language.as_ref() == Some(&language) => language,
+                None => return None,
+            };
+
+            Some(CaptionTrack {
+                language,
+                url: vtt_url(subtitles)?,
+            })
+        })
+        .collect::<Vec<_>>();
+    for caption_track in automatic_captions {
+        let language = &caption_track.language;
+        if !caption_tracks
+            .iter()
+            .any(|track| &track.language == language)
+        {
+            caption_tracks.push(caption_track);
+        }
+    }
+
+    Ok(caption_tracks)
+}
+
+/// Fetches the captions in WebVTT format in the provided language for the provided YouTube video
+/// ID.
 ///
-/// If the result is [`Ok`], the redirect URL will be cached for 24 hours for the given video ID.
-#[cached(key = "String", convert = r#"{ video_id.to_owned() }"#, ttl = 86400)]
-async fn retrieve_redirect_url(client: &Client, video_id: &str) -> Result<String> {
+/// The caption tracks are determined again, so that the URL of the captions is still valid.
+async fn fetch_transcript(
+    youtube_dl: YoutubeDlOptions,
+    client: reqwest::Client,
+    video_id: String,
+    language: String,
+) -> Result<String> {
+    let caption_track = fetch_caption_tracks(youtube_dl, video_id.clone())
+        .await?
+        .into_iter()
+        .find(|caption_track| caption_track.language == language)
+        .ok_or(Error::NoTranscriptFound)?;
+
+    println!("⏬ Retrieving {language} captions of video {video_id}...");
+    let response = client
+        .get(caption_track.url)
+        .send()
+        .await?
+        .error_for_status()?;
+    let transcript = response.text().await?;
+
+    Ok(transcript)
+}
+
+/// Retrieves the redirect URL for the provided YouTube video ID.
+async fn retrieve_redirect_url(client: Client, video_id: String) -> Result<String> {
     let video_id = video_id.parse()?;
     let video = client.video(video_id).await?;
     let stream = video
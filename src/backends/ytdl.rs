@@ -0,0 +1,327 @@
+//! The generic yt-dlp back-end.
+//!
+//! It uses youtube-dl (or yt-dlp) to retrieve the feed (playlist, channel, etc.) and items
+//! (entries) from any site that it supports. The channel ID is the URL of the playlist.
+//!
+//! Because yt-dlp is run on the URLs, only public URLs or URLs matching the configured URL
+//! patterns are accepted, and only the URLs of entries of retrieved playlists are downloaded.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use reqwest::Url;
+use youtube_dl::{Playlist, SingleVideo, YoutubeDlOutput};
+
+use super::{
+    channel_categories, file_url, is_allowed_url, mime_extension, url_file, BackendConfig,
+    BackendInfo, Channel, Enclosure, Item, Medium, YoutubeDlOptions,
+};
+use crate::cache::{Cache, CacheKind};
+use crate::{Error, Result};
+
+/// The default bitrate used for estimating file sizes.
+const DEFAULT_BITRATE: u64 = 128 * 1024;
+
+/// The default file (MIME) type, matching the preferred format (see [`FORMAT`]).
+const DEFAULT_FILE_TYPE: &str = "audio/mp4";
+
+/// The format that is selected when resolving the redirect URL of an entry.
+///
+/// Only formats in the M4A/MP4 container format that are directly available (so no HLS or DASH)
+/// are selected, so that the content matches the MIME type of the enclosures (see
+/// [`DEFAULT_FILE_TYPE`]). An audio-only format is preferred; clients play the audio of a format
+/// that also has a video stream.
+const FORMAT: &str = "bestaudio[ext=m4a][protocol^=http]/bestaudio[ext=mp4][protocol^=http]/\
+                      best[ext=mp4][protocol^=http]";
+
+/// The default item limit.
+const DEFAULT_ITEM_LIMIT: usize = 50;
+
+/// The information of the yt-dlp back-end.
+pub(crate) const INFO: BackendInfo = BackendInfo {
+    id: "ytdl",
+    name: "yt-dlp",
+    description: "URL-encoded playlist URL of any site supported by yt-dlp",
+    url_patterns: &[],
+};
+
+/// Creates a generic yt-dlp back-end.
+pub(crate) fn backend(cache: &Cache, settings: &BackendConfig) -> Backend {
+    Backend {
+        youtube_dl: settings.youtube_dl_options(),
+        url_patterns: settings.url_patterns.clone(),
+        cache: cache.scoped("ytdl", settings.ttl),
+    }
+}
+
+/// The generic yt-dlp back-end.
+pub struct Backend {
+    /// The options used for running `yt-dlp`.
+    youtube_dl: YoutubeDlOptions,
+
+    /// The configured URL patterns of the playlists that are allowed even if they are not public.
+    url_patterns: Vec<String>,
+
+    /// The cache store used for playlists and redirect URLs.
+    cache: Cache,
+}
+
+#[async_trait]
+impl super::Backend for Backend {
+    fn name(&self) -> &'static str {
+        "yt-dlp"
+    }
+
+    async fn channel(&self, channel_id: &str, item_limit: Option<usize>) -> Result<Channel> {
+        // For yt-dlp a channel ID is the URL of a playlist (or channel, user, etc.) on any
+        // supported site.
+        let url = Url::parse(channel_id)?;
+        if !is_allowed_url(&url, &self.url_patterns).await {
+            return Err(Error::UnsupportedChannel(channel_id.to_string()));
+        }
+        let limit = item_limit.unwrap_or(DEFAULT_ITEM_LIMIT);
+        let key = format!("{limit}:{url}");
+
+        // The channel and its items are extracted at once, so they are cached as items.
+        println!("⏬ Retrieving playlist from {url}...");
+        self.cache
+            .get_or_fetch(
+                CacheKind::Items,
+                &key,
+                fetch_playlist(self.youtube_dl.clone(), self.cache.clone(), url, limit),
+            )
+            .await
+    }
+
+    async fn redirect_url(&self, file: &Path) -> Result<String> {
+        // The download file path encodes the URL of an entry. Only HTTP(S) URLs are accepted, so
+        // that the file path cannot be used to pass options to yt-dlp, and only the URLs of
+        // entries of playlists that have been retrieved, so that yt-dlp is not run on arbitrary
+        // URLs.
+        let url = file_url(file).ok_or(Error::NoRedirectUrlFound)?;
+        self.cache
+            .peek::<String>(CacheKind::Items, &entry_key(&url))?
+            .ok_or(Error::NoRedirectUrlFound)?;
+
+        self.cache
+            .get_or_fetch(
+                CacheKind::RedirectUrl,
+                url.as_str(),
+                retrieve_redirect_url(self.youtube_dl.clone(), url.to_string()),
+            )
+            .await
+    }
+}
+
+/// A youtube-dl playlist with the URL it was extracted from.
+struct PlaylistWithUrl(Playlist, Url);
+
+/// A youtube-dl (flat) playlist entry with its position in the playlist and the timestamp of the
+/// newest entry in the playlist.
+struct IndexedEntry(usize, SingleVideo, DateTime<Utc>);
+
+impl From<PlaylistWithUrl> for Channel {
+    fn from(PlaylistWithUrl(playlist, url): PlaylistWithUrl) -> Self {
+        let site = playlist
+            .extractor_key
+            .clone()
+            .unwrap_or_else(|| String::from("yt-dlp"));
+        let name = playlist
+            .title
+            .or(playlist.id)
+            .unwrap_or_else(|| url.to_string());
+        let link = playlist
+            .webpage_url
+            .and_then(|url| Url::parse(&url).ok())
+            .unwrap_or(url);
+        let image = playlist.thumbnails.and_then(|thumbnails| {
+            thumbnails
+                .into_iter()
+                .filter_map(|thumbnail| thumbnail.url)
+                .filter_map(|url| Url::parse(&url).ok())
+                .next_back()
+        });
+        let entries = playlist.entries.unwrap_or_default();
+        let newest_timestamp = entries
+            .iter()
+            .filter_map(entry_timestamp)
+            .max()
+            .unwrap_or(DateTime::UNIX_EPOCH);
+        let entry_categories = entries
+            .iter()
+            .filter_map(|entry| entry.categories.clone())
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        let categories = channel_categories(entry_categories);
+        let items = entries
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                Item::try_from(IndexedEntry(index, entry, newest_timestamp)).ok()
+            })
+            .collect();
+
+        Channel {
+            title: format!("{name} (via {site})"),
+            link,
+            description: String::new(),
+            author: playlist.uploader,
+            categories,
+            image,
+            images: BTreeMap::new(),
+            medium: Medium::Podcast,
+            items,
+        }
+    }
+}
+
+impl TryFrom<IndexedEntry> for Item {
+    type Error = Error;
+
+    fn try_from(IndexedEntry(index, entry, newest_timestamp): IndexedEntry) -> Result<Self> {
+        let link = entry
+            .webpage_url
+            .as_ref()
+            .or(entry.url.as_ref())
+            .ok_or(Error::NoRedirectUrlFound)?;
+        let link = Url::parse(link)?;
+        // Flat playlist entries often lack a timestamp, so they get one based on their position in
+        // the playlist relative to the newest entry to keep them in order. This way, it does not
+        // change every time the playlist is retrieved.
+        let published_at = entry_timestamp(&entry)
+            .unwrap_or_else(|| newest_timestamp - Duration::seconds(index as i64));
+        let extension = mime_extension(DEFAULT_FILE_TYPE).expect("MIME type has extension");
+        let duration = entry
+            .duration
+            .as_ref()
+            .and_then(|duration| duration.as_f64())
+            .map(|duration| duration as u32);
+        let enclosure = Enclosure {
+            file: url_file(link.as_str(), extension),
+            mime_type: String::from(DEFAULT_FILE_TYPE),
+            length: duration.map(estimated_file_size).unwrap_or_default(),
+        };
+        let description = entry
+            .description
+            .filter(|description| !description.is_empty())
+            .unwrap_or_else(|| format!("Taken from: {link}"));
+        let image = entry
+            .thumbnail
+            .or_else(|| {
+                entry.thumbnails.and_then(|thumbnails| {
+                    thumbnails.into_iter().filter_map(|tn| tn.url).next_back()
+                })
+            })
+            .and_then(|url| Url::parse(&url).ok());
+        let keywords = entry
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Ok(Item {
+            title: entry.title.unwrap_or_else(|| entry.id.clone()),
+            link,
+            description: Some(description),
+            categories: Default::default(),
+            enclosure,
+            duration,
+            chapters: Vec::new(),
+            transcripts: Vec::new(),
+            guid: entry.id,
+            keywords,
+            season: None,
+            episode: None,
+            image,
+            published_at,
+            // There is no updated at timestamp available, really.
+            updated_at: published_at,
+        })
+    }
+}
+
+/// Returns the timestamp or otherwise the upload date of the entry (if any).
+fn entry_timestamp(entry: &SingleVideo) -> Option<DateTime<Utc>> {
+    entry
+        .timestamp
+        .and_then(|timestamp| Utc.timestamp_opt(timestamp as i64, 0).single())
+        .or_else(|| entry.upload_date.as_deref().and_then(parse_upload_date))
+}
+
+/// Parses an upload date (`YYYYMMDD`) as provided by youtube-dl.
+fn parse_upload_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(date, "%Y%m%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .map(|timestamp| timestamp.and_utc())
+}
+
+/// Returns the estimated file size in bytes for a given duration.
+///
+/// It uses the default bitrate (see [`DEFAULT_BITRATE`]) which is in B/s.
+fn estimated_file_size(duration: u32) -> u64 {
+    DEFAULT_BITRATE * duration as u64 / 8
+}
+
+/// Returns the cache key under which the URL of an entry of a retrieved playlist is stored.
+fn entry_key(url: &Url) -> String {
+    format!("entry:{url}")
+}
+
+/// Fetches the playlist at the URL (up to the given limit) using a flat playlist extraction.
+///
+/// If the URL points to a single entry, a channel with only that item is returned. The URLs of
+/// the entries are stored in the cache, so that they are known when the media content is
+/// downloaded.
+async fn fetch_playlist(
+    youtube_dl: YoutubeDlOptions,
+    cache: Cache,
+    url: Url,
+    limit: usize,
+) -> Result<Channel> {
+    let output = youtube_dl
+        .command(url.as_str())
+        .flat_playlist(true)
+        .extra_arg("--playlist-end")
+        .extra_arg(limit.to_string())
+        .run_async()
+        .await?;
+
+    let playlist = match output {
+        YoutubeDlOutput::Playlist(playlist) => *playlist,
+        YoutubeDlOutput::SingleVideo(entry) => Playlist {
+            extractor_key: entry.extractor_key.clone(),
+            id: Some(entry.id.clone()),
+            title: entry.title.clone(),
+            uploader: entry.uploader.clone(),
+            webpage_url: entry.webpage_url.clone(),
+            thumbnails: entry.thumbnails.clone(),
+            entries: Some(Vec::from([*entry])),
+            ..Default::default()
+        },
+    };
+
+    let channel = Channel::from(PlaylistWithUrl(playlist, url));
+    for item in &channel.items {
+        if let Some(url) = file_url(&item.enclosure.file) {
+            cache.store(CacheKind::Items, &entry_key(&url), &url.to_string())?;
+        }
+    }
+
+    Ok(channel)
+}
+
+/// Retrieves the redirect URL for the provided entry URL.
+async fn retrieve_redirect_url(youtube_dl: YoutubeDlOptions, url: String) -> Result<String> {
+    println!("🌍 Determining direct URL for {url}...");
+    let output = youtube_dl.command(url).format(FORMAT).run_async().await?;
+
+    match output {
+        YoutubeDlOutput::SingleVideo(entry) => entry.url.ok_or(Error::NoRedirectUrlFound),
+        _ => Err(Error::NoRedirectUrlFound),
+    }
+}
@@ -0,0 +1,424 @@
+//! The persistent cache store shared by all back-ends.
+//!
+//! The cache is stored in an embedded database so that cached channels, items and redirect URLs
+//! survive restarts. Every entry has an expiry timestamp that is stored alongside its value, so
+//! TTLs are preserved across restarts as well. If the total size of the cache exceeds the
+//! configured maximum size, the entries that expire first are evicted.
+//!
+//! Expired entries are kept for a while longer so that they can still be served (stale) while
+//! they are being revalidated in the background.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use rocket::serde::json::{from_slice, serde_json};
+use rocket::serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::Result;
+
+/// The default time-to-live of cache entries (in seconds).
+const DEFAULT_TTL: u64 = 86400;
+
+/// The default maximum size of the cache (in bytes).
+const DEFAULT_MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The default maximum time expired cache entries can still be served (in seconds).
+const DEFAULT_MAX_STALE: u64 = 86400;
+
+/// The size of the expiry timestamp prefix of stored values (in bytes).
+const EXPIRY_PREFIX_SIZE: usize = size_of::<i64>();
+
+/// The cache configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct CacheConfig {
+    /// The path of the directory of the persistent cache store.
+    ///
+    /// If not set, a temporary cache store is used that does not survive restarts.
+    path: Option<PathBuf>,
+
+    /// The maximum size of the cache (in bytes).
+    max_size: u64,
+
+    /// The maximum time an expired entry can still be served while it is revalidated (in
+    /// seconds).
+    max_stale: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_size: DEFAULT_MAX_SIZE,
+            max_stale: DEFAULT_MAX_STALE,
+        }
+    }
+}
+
+/// The time-to-live configuration of the cache entries of a back-end (in seconds).
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CacheTtls {
+    /// The time-to-live of channel metadata.
+    channel: u64,
+
+    /// The time-to-live of (content) item lists.
+    items: u64,
+
+    /// The time-to-live of redirect URLs.
+    redirect_url: u64,
+}
+
+impl CacheTtls {
+    /// Returns the time-to-live for the given cache kind.
+    pub(crate) fn get(&self, kind: CacheKind) -> Duration {
+        let ttl = match kind {
+            CacheKind::Channel => self.channel,
+            CacheKind::Items => self.items,
+            CacheKind::RedirectUrl => self.redirect_url,
+        };
+
+        Duration::seconds(ttl as i64)
+    }
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            channel: DEFAULT_TTL,
+            items: DEFAULT_TTL,
+            redirect_url: DEFAULT_TTL,
+        }
+    }
+}
+
+/// The kinds of data that can be cached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheKind {
+    /// The metadata of a channel.
+    Channel,
+
+    /// The (content) items of a channel.
+    Items,
+
+    /// The redirect URL of a download file.
+    RedirectUrl,
+}
+
+impl CacheKind {
+    /// Returns the name of the cache kind (used in cache keys).
+    fn as_str(&self) -> &'static str {
+        match self {
+            CacheKind::Channel => "channel",
+            CacheKind::Items => "items",
+            CacheKind::RedirectUrl => "redirect_url",
+        }
+    }
+}
+
+/// The persistent cache store.
+///
+/// A cache can be cheaply cloned; all clones share the same underlying store.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    /// The embedded database that stores the cache entries.
+    db: sled::Db,
+
+    /// The scope (e.g. back-end ID) the cache keys are prefixed with.
+    scope: String,
+
+    /// The time-to-live of the entries per cache kind.
+    ttls: CacheTtls,
+
+    /// The time before expiry from which entries are refreshed right away.
+    ///
+    /// This is zero, except for handles used for refreshing entries ahead of time.
+    refresh_ahead: Duration,
+
+    /// The maximum size of the cache (in bytes).
+    max_size: u64,
+
+    /// The maximum time an expired entry can still be served while it is being revalidated.
+    max_stale: Duration,
+
+    /// The current (approximate) size of the cache (in bytes).
+    size: Arc<AtomicU64>,
+
+    /// The full keys of the entries that are currently being fetched in the background.
+    fetching: Arc<Mutex<HashSet<String>>>,
+}
+
+/// A cache entry.
+#[derive(Debug)]
+struct Entry<T> {
+    /// The timestamp the entry expires.
+    expires_at: DateTime<Utc>,
+
+    /// The cached value.
+    value: T,
+}
+
+impl Cache {
+    /// Opens the cache store using the provided configuration.
+    ///
+    /// Entries that are expired for longer than they can be served stale are removed from the
+    /// store right away.
+    pub(crate) fn open(config: &CacheConfig) -> Result<Self> {
+        let db = match &config.path {
+            Some(path) => sled::open(path)?,
+            None => sled::Config::new().temporary(true).open()?,
+        };
+        let cache = Self {
+            db,
+            scope: String::new(),
+            ttls: CacheTtls::default(),
+            refresh_ahead: Duration::zero(),
+            max_size: config.max_size,
+            max_stale: Duration::seconds(config.max_stale as i64),
+            size: Arc::new(AtomicU64::new(0)),
+            fetching: Arc::new(Mutex::new(HashSet::new())),
+        };
+
+        let discard_before = (Utc::now() - cache.max_stale).timestamp();
+        let mut size = 0;
+        for entry in cache.db.iter() {
+            let (key, value) = entry?;
+            if expiry_timestamp(&value) < discard_before {
+                cache.db.remove(&key)?;
+            } else {
+                size += (key.len() + value.len()) as u64;
+            }
+        }
+        cache.size.store(size, Ordering::Relaxed);
+
+        Ok(cache)
+    }
+
+    /// Returns a handle to the cache whose keys are scoped by the provided name.
+    ///
+    /// The entries inserted via this handle will use the provided time-to-live configuration.
+    pub fn scoped(&self, scope: &str, ttls: CacheTtls) -> Self {
+        Self {
+            scope: scope.to_owned(),
+            ttls,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle to the cache that refreshes entries that expire within the provided
+    /// time right away.
+    ///
+    /// Entries are never refreshed before the second half of their lifetime though, so that
+    /// entries with a short time-to-live are not refreshed every time.
+    pub(crate) fn refreshing_ahead(&self, refresh_ahead: Duration) -> Self {
+        Self {
+            refresh_ahead,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the cached value of the given kind for the given key or fetches it otherwise.
+    ///
+    /// If the cached value is expired (but not for too long), it is returned anyway and
+    /// revalidated by fetching it in the background. If the fetch result is [`Ok`], the value
+    /// will be cached for the TTL of the cache kind.
+    pub async fn get_or_fetch<T, F>(&self, kind: CacheKind, key: &str, fetch: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let cache_key = self.cache_key(kind, key);
+        if let Some(entry) = self.get(&cache_key)? {
+            let now = Utc::now();
+            let refresh_ahead = self.refresh_ahead.min(self.ttls.get(kind) / 2);
+            if entry.expires_at - refresh_ahead > now {
+                return Ok(entry.value);
+            } else if entry.expires_at <= now && self.refresh_ahead.is_zero() {
+                self.fetch_in_background(kind, cache_key, fetch);
+                return Ok(entry.value);
+            }
+        }
+
+        let value = fetch.await?;
+        self.insert(&cache_key, &value, self.ttls.get(kind))?;
+
+        Ok(value)
+    }
+
+    /// Returns the full key of a cache entry.
+    fn cache_key(&self, kind: CacheKind, key: &str) -> String {
+        format!("{}:{}:{key}", self.scope, kind.as_str())
+    }
+
+    /// Returns the cached entry for the given full key, if present and not expired for too long.
+    ///
+    /// Entries that are expired for too long or cannot be deserialized anymore are removed.
+    fn get<T: DeserializeOwned>(&self, cache_key: &str) -> Result<Option<Entry<T>>> {
+        let Some(stored) = self.db.get(cache_key)? else {
+            return Ok(None);
+        };
+
+        let expires_at = DateTime::from_timestamp(expiry_timestamp(&stored), 0);
+        if let Some(expires_at) = expires_at.filter(|&ts| ts + self.max_stale >= Utc::now()) {
+            if let Ok(value) = from_slice(&stored[EXPIRY_PREFIX_SIZE..]) {
+                return Ok(Some(Entry { expires_at, value }));
+            }
+        }
+        self.remove(cache_key)?;
+
+        Ok(None)
+    }
+
+    /// Returns the cached value of the given kind for the given key, if present.
+    ///
+    /// The value is returned even if it is expired (but not for too long), and it is never
+    /// fetched.
+    pub fn peek<T>(&self, kind: CacheKind, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let entry = self.get(&self.cache_key(kind, key))?;
+
+        Ok(entry.map(|entry| entry.value))
+    }
+
+    /// Stores the value of the given kind for the given key, replacing the cached value (if any).
+    pub fn store<T: Serialize>(&self, kind: CacheKind, key: &str, value: &T) -> Result<()> {
+        self.insert(&self.cache_key(kind, key), value, self.ttls.get(kind))
+    }
+
+    /// Removes the cached value of the given kind for the given key (if any).
+    ///
+    /// This can be used to discard values that turned out to be no longer valid.
+    pub fn invalidate(&self, kind: CacheKind, key: &str) -> Result<()> {
+        self.remove(&self.cache_key(kind, key))
+    }
+
+    /// Fetches the value of the given kind for the given key in the background, if it is not
+    /// cached yet.
+    ///
+    /// This can be used to cache values that will be needed later ahead of time.
+    pub fn prefetch<T, F>(&self, kind: CacheKind, key: &str, fetch: F) -> Result<()>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let cache_key = self.cache_key(kind, key);
+        if self.get::<T>(&cache_key)?.is_none() {
+            self.fetch_in_background(kind, cache_key, fetch);
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the entry for the given full key in the background and caches it.
+    ///
+    /// If the entry is already being fetched in the background, nothing happens.
+    fn fetch_in_background<T, F>(&self, kind: CacheKind, cache_key: String, fetch: F)
+    where
+        T: Serialize + Send + 'static,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let mut fetching = self.fetching.lock().expect("lock is not poisoned");
+        if !fetching.insert(cache_key.clone()) {
+            return;
+        }
+        drop(fetching);
+
+        let cache = self.clone();
+        tokio::spawn(async move {
+            println!("🔄 Fetching cache entry {cache_key} in the background...");
+            let result = match fetch.await {
+                Ok(value) => cache.insert(&cache_key, &value, cache.ttls.get(kind)),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                eprintln!("💥 Could not fetch cache entry {cache_key}: {err}");
+            }
+
+            cache
+                .fetching
+                .lock()
+                .expect("lock is not poisoned")
+                .remove(&cache_key);
+        });
+    }
+
+    /// Inserts the value for the given full key so that it expires after the TTL.
+    ///
+    /// If the cache exceeds its maximum size afterwards, entries are evicted.
+    fn insert<T: Serialize>(&self, cache_key: &str, value: &T, ttl: Duration) -> Result<()> {
+        let expires_at: DateTime<Utc> = Utc::now() + ttl;
+        let mut stored = expires_at.timestamp().to_be_bytes().to_vec();
+        serde_json::to_writer(&mut stored, value)?;
+
+        let added = (cache_key.len() + stored.len()) as u64;
+        if let Some(old) = self.db.insert(cache_key, stored)? {
+            self.shrink((cache_key.len() + old.len()) as u64);
+        }
+        if self.size.fetch_add(added, Ordering::Relaxed) + added > self.max_size {
+            self.evict()?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the entry for the given full key.
+    fn remove(&self, cache_key: &str) -> Result<()> {
+        if let Some(old) = self.db.remove(cache_key)? {
+            self.shrink((cache_key.len() + old.len()) as u64);
+        }
+
+        Ok(())
+    }
+
+    /// Shrinks the current size of the cache by the given number of bytes.
+    fn shrink(&self, bytes: u64) {
+        let _ = self
+            .size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |size| {
+                Some(size.saturating_sub(bytes))
+            });
+    }
+
+    /// Evicts the entries that expire first until the cache is within its maximum size.
+    fn evict(&self) -> Result<()> {
+        let mut entries = self
+            .db
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok((expiry_timestamp(&value), key))
+            })
+            .collect::<Result<Vec<_>, sled::Error>>()?;
+        entries.sort_unstable_by_key(|(expires_at, _)| *expires_at);
+
+        println!("🧹 Cache exceeds its maximum size, evicting entries...");
+        for (_, key) in entries {
+            if self.size.load(Ordering::Relaxed) <= self.max_size {
+                break;
+            }
+            if let Some(old) = self.db.remove(&key)? {
+                self.shrink((key.len() + old.len()) as u64);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the expiry timestamp of a stored value.
+///
+/// Values that are too short to contain an expiry timestamp are considered to be expired (for
+/// a very long time).
+fn expiry_timestamp(stored: &[u8]) -> i64 {
+    stored
+        .get(..EXPIRY_PREFIX_SIZE)
+        .and_then(|prefix| prefix.try_into().ok())
+        .map(i64::from_be_bytes)
+        .unwrap_or(i64::MIN)
+}
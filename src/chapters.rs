@@ -0,0 +1,168 @@
+//! Chapters of the media content of items.
+//!
+//! The chapters are parsed from item descriptions by some back-ends. They are served as a JSON
+//! chapters document (see the
+//! [`podcast:chapters`](https://podcasting2.org/podcast-namespace/tags/chapters) tag) that is
+//! looked up in the items of the channel the item belongs to.
+
+use std::path::Path;
+
+use rocket::serde::Serialize;
+
+use crate::backends::{Chapter, Item};
+
+/// The version of the JSON chapters format.
+const VERSION: &str = "1.2.0";
+
+/// The MIME type of a JSON chapters document.
+pub(crate) const MIME_TYPE: &str = "application/json+chapters";
+
+/// A JSON chapters document.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Document {
+    /// The version of the JSON chapters format.
+    version: &'static str,
+
+    /// The chapters of the media content.
+    chapters: Vec<Chapter>,
+}
+
+/// Looks up the chapters document for the provided download file path in the provided items.
+///
+/// If there is no item with the download file path or it has no chapters, [`None`] is returned.
+pub(crate) fn lookup(items: Vec<Item>, file: &Path) -> Option<Document> {
+    let item = items
+        .into_iter()
+        .find(|item| item.enclosure.file == file && !item.chapters.is_empty())?;
+
+    Some(Document {
+        version: VERSION,
+        chapters: item.chapters,
+    })
+}
+
+/// Parses chapters from the timestamps in the provided (item) description.
+///
+/// Each line that starts with a timestamp (`[H:]MM:SS`, possibly after some punctuation) marks the
+/// start of a chapter, the rest of the line is its title. Like YouTube does, the chapters are
+/// only used if there are at least two, the first starts at zero and they are in order.
+pub(crate) fn parse_chapters(description: &str) -> Vec<Chapter> {
+    let chapters = description
+        .lines()
+        .filter_map(|line| {
+            let line =
+                line.trim_start_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation());
+            let (timestamp, title) = line.split_once(char::is_whitespace)?;
+            let timestamp = timestamp.trim_end_matches(|c: char| c.is_ascii_punctuation());
+            // A number of seconds on its own is too likely to be something else, like a list item.
+            if !timestamp.contains(':') {
+                return None;
+            }
+            let start_time = parse_timestamp(timestamp)?;
+            let title = title.trim_matches(|c: char| c.is_whitespace() || "-–—:|)]".contains(c));
+
+            (!title.is_empty()).then(|| Chapter {
+                start_time,
+                title: String::from(title),
+            })
+        })
+        .collect::<Vec<_>>();
+    let in_order = chapters
+        .windows(2)
+        .all(|pair| pair[0].start_time < pair[1].start_time);
+
+    if chapters.len() >= 2 && chapters[0].start_time == 0 && in_order {
+        chapters
+    } else {
+        Vec::new()
+    }
+}
+
+/// Parses a timestamp of the form `[[H:]MM:]SS[.mmm]` to a number of seconds.
+///
+/// The first part can have any number of digits, e.g. a number of seconds on its own or more than
+/// 99 minutes. The fraction of the seconds is ignored.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<u32> {
+    let timestamp = timestamp.trim();
+    let (timestamp, fraction) = timestamp.split_once('.').unwrap_or((timestamp, ""));
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let parts = timestamp.split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    parts
+        .into_iter()
+        .enumerate()
+        .try_fold(0, |total: u32, (index, part)| {
+            let is_valid = !part.is_empty() && (index == 0 || part.len() <= 2);
+            if !is_valid || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let value = part.parse::<u32>().ok()?;
+
+            total.checked_mul(60)?.checked_add(value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the start times and titles of the chapters parsed from the description.
+    fn chapters(description: &str) -> Vec<(u32, String)> {
+        parse_chapters(description)
+            .into_iter()
+            .map(|chapter| (chapter.start_time, chapter.title))
+            .collect()
+    }
+
+    #[test]
+    fn parse_timestamp_forms() {
+        assert_eq!(parse_timestamp("1:02:03"), Some(3723));
+        assert_eq!(parse_timestamp("02:03"), Some(123));
+        assert_eq!(parse_timestamp("123:45"), Some(7425));
+        assert_eq!(parse_timestamp("3600"), Some(3600));
+        assert_eq!(parse_timestamp("00:01:02.500"), Some(62));
+        assert_eq!(parse_timestamp("1:02:03:04"), None);
+        assert_eq!(parse_timestamp("1:023"), None);
+        assert_eq!(parse_timestamp("1::03"), None);
+        assert_eq!(parse_timestamp("1:0x"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    #[test]
+    fn parse_chapters_with_punctuation() {
+        let description = "Tracklist:\n\
+                           00:00 - Intro\n\
+                           (02:03) First part\n\
+                           1:02:03. Second part\n\
+                           \n\
+                           Thanks for listening!";
+        assert_eq!(
+            chapters(description),
+            [
+                (0, String::from("Intro")),
+                (123, String::from("First part")),
+                (3723, String::from("Second part"))
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_chapters_unordered_or_duplicate() {
+        assert!(chapters("00:00 Intro\n05:00 Second\n02:00 Third").is_empty());
+        assert!(chapters("00:00 Intro\n00:00 Second\n01:00 Third").is_empty());
+        assert!(chapters("01:00 Intro\n02:00 Second").is_empty());
+    }
+
+    #[test]
+    fn parse_chapters_without_chapters() {
+        assert!(chapters("").is_empty());
+        assert!(chapters("Just a description.\n1. First item\n2. Second item").is_empty());
+        assert!(chapters("00:00 Only a single chapter").is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+//! Helpers for serving downloads.
+//!
+//! A download can either be served by redirecting the client to the URL resolved by the
+//! back-end, by proxying it, i.e. by streaming the upstream media content to the client, by
+//! transcoding it (see [`crate::transcode`]) or from a local file (see [`crate::archive`]).
+
+use std::convert::Infallible;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use reqwest::header::{HeaderName, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use rocket::futures::TryStreamExt;
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Redirect, Responder, Response};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::Request;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::StreamReader;
+
+use crate::transcode::TranscodedMedia;
+use crate::{Error, Result};
+
+/// The upstream response headers that are forwarded to the client when proxying.
+const FORWARDED_HEADERS: [HeaderName; 4] =
+    [ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE];
+
+/// The ways a download can be served.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub(crate) enum DownloadMode {
+    /// Redirect the client to the URL resolved by the back-end.
+    #[default]
+    Redirect,
+
+    /// Stream the media content from the URL resolved by the back-end to the client.
+    Proxy,
+}
+
+/// A download that is served in one of the download modes.
+#[derive(Debug, Responder)]
+pub(crate) enum Download {
+    /// A download served by redirecting.
+    Redirect(Redirect),
+
+    /// A download served by proxying.
+    Proxy(ProxiedMedia),
+
+    /// A download served by transcoding.
+    Transcode(TranscodedMedia),
+
+    /// A download served from a local file.
+    Local(LocalMedia),
+
+    /// A download served from in-memory data.
+    Data((ContentType, Vec<u8>)),
+}
+
+/// A request guard for the (optional) range that the client requests.
+#[derive(Debug)]
+pub(crate) struct RangeHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let range = request.headers().get_one("Range").map(String::from);
+
+        Outcome::Success(RangeHeader(range))
+    }
+}
+
+impl RangeHeader {
+    /// Returns the first byte range requested for content of the provided length.
+    ///
+    /// Only single byte ranges are supported; if the range cannot be parsed, [`None`] is
+    /// returned and the full content should be served.
+    fn byte_range(&self, length: u64) -> Result<Option<(u64, u64)>> {
+        let Some((start, end)) = self
+            .0
+            .as_deref()
+            .and_then(|range| range.strip_prefix("bytes="))
+            .filter(|spec| !spec.contains(','))
+            .and_then(|spec| spec.trim().split_once('-'))
+        else {
+            return Ok(None);
+        };
+
+        let last = length.saturating_sub(1);
+        let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(last)),
+            (Ok(start), Err(_)) if end.is_empty() => (start, last),
+            (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+                (length.saturating_sub(suffix), last)
+            }
+            _ => return Ok(None),
+        };
+        if range.0 >= length {
+            return Err(Error::RangeNotSatisfiable);
+        }
+
+        Ok(Some(range))
+    }
+}
+
+/// Media content that is served from a local file.
+#[derive(Debug)]
+pub(crate) struct LocalMedia {
+    /// The opened local file.
+    file: File,
+
+    /// The content type of the local file.
+    content_type: ContentType,
+
+    /// The length of the local file (in bytes).
+    length: u64,
+
+    /// The requested (inclusive) byte range of the local file (if any).
+    range: Option<(u64, u64)>,
+}
+
+impl<'r> Responder<'r, 'static> for LocalMedia {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        let LocalMedia {
+            file,
+            content_type,
+            length,
+            range,
+        } = self;
+        let mut builder = Response::build();
+
+        builder
+            .header(content_type)
+            .raw_header("Accept-Ranges", "bytes");
+        match range {
+            Some((start, end)) => {
+                let range_length = end - start + 1;
+
+                builder
+                    .status(Status::PartialContent)
+                    .raw_header("Content-Range", format!("bytes {start}-{end}/{length}"))
+                    .raw_header("Content-Length", range_length.to_string())
+                    .streamed_body(file.take(range_length));
+            }
+            None => {
+                builder
+                    .raw_header("Content-Length", length.to_string())
+                    .streamed_body(file);
+            }
+        }
+
+        Ok(builder.finalize())
+    }
+}
+
+/// Media content that is streamed from an upstream response.
+#[derive(Debug)]
+pub(crate) struct ProxiedMedia(reqwest::Response);
+
+impl<'r> Responder<'r, 'static> for ProxiedMedia {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        let ProxiedMedia(upstream) = self;
+        let mut builder = Response::build();
+
+        builder.status(Status::new(upstream.status().as_u16()));
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = upstream.headers().get(&name) {
+                let value = value.to_str().map_err(|_| Status::BadGateway)?;
+                builder.raw_header(name.as_str().to_owned(), value.to_owned());
+            }
+        }
+        let stream = upstream.bytes_stream().map_err(std::io::Error::other);
+        builder.streamed_body(StreamReader::new(stream));
+
+        Ok(builder.finalize())
+    }
+}
+
+/// Starts proxying the media content at the provided URL using the HTTP client of the back-end.
+///
+/// If a range is provided, only that range of the media content is requested upstream.
+pub(crate) async fn proxy(
+    client: &reqwest::Client,
+    url: &str,
+    range: RangeHeader,
+) -> Result<ProxiedMedia> {
+    println!("🔀 Proxying media content from {url}...");
+    let mut request = client.get(url);
+    if let RangeHeader(Some(range)) = range {
+        request = request.header(reqwest::header::RANGE, range);
+    }
+    let response = request.send().await?;
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        return Err(Error::RangeNotSatisfiable);
+    }
+    let response = response.error_for_status()?;
+
+    Ok(ProxiedMedia(response))
+}
+
+/// Opens the local file at the provided path for serving.
+///
+/// If a range is provided, only that range of the file will be served.
+pub(crate) async fn local(path: &Path, range: RangeHeader) -> Result<LocalMedia> {
+    let mut file = File::open(path).await?;
+    let length = file.metadata().await?.len();
+    let content_type = path
+        .extension()
+        .and_then(|ext| mime_db::lookup(ext.to_string_lossy()))
+        .and_then(ContentType::parse_flexible)
+        .unwrap_or(ContentType::Binary);
+    let range = range.byte_range(length)?;
+    if let Some((start, _)) = range {
+        file.seek(SeekFrom::Start(start)).await?;
+    }
+
+    Ok(LocalMedia {
+        file,
+        content_type,
+        length,
+        range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the byte range of the provided range header for content of 100 bytes.
+    fn byte_range(range: &str) -> Result<Option<(u64, u64)>> {
+        RangeHeader(Some(String::from(range))).byte_range(100)
+    }
+
+    #[test]
+    fn byte_range_forms() {
+        assert_eq!(RangeHeader(None).byte_range(100).unwrap(), None);
+        assert_eq!(byte_range("bytes=0-").unwrap(), Some((0, 99)));
+        assert_eq!(byte_range("bytes=10-20").unwrap(), Some((10, 20)));
+        assert_eq!(byte_range("bytes=90-200").unwrap(), Some((90, 99)));
+        assert_eq!(byte_range("bytes=-10").unwrap(), Some((90, 99)));
+        assert_eq!(byte_range("bytes=-200").unwrap(), Some((0, 99)));
+    }
+
+    #[test]
+    fn byte_range_unsatisfiable() {
+        assert!(matches!(
+            byte_range("bytes=100-"),
+            Err(Error::RangeNotSatisfiable)
+        ));
+        assert!(matches!(
+            RangeHeader(Some(String::from("bytes=0-"))).byte_range(0),
+            Err(Error::RangeNotSatisfiable)
+        ));
+    }
+
+    #[test]
+    fn byte_range_malformed_or_multiple() {
+        for range in [
+            "bytes=20-10",
+            "bytes=abc",
+            "bytes=a-b",
+            "bytes=-0",
+            "items=0-10",
+            "bytes=0-10,20-30",
+        ] {
+            assert_eq!(byte_range(range).unwrap(), None, "{range} is parsed");
+        }
+    }
+}
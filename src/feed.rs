@@ -1,42 +1,271 @@
-//! Helper functions for constructing RSS feeds.
+//! Helper functions for constructing feeds.
+//!
+//! Feeds are constructed as RSS by default, but can also be constructed as Atom (see [`atom`]) or
+//! JSON Feed (see [`json_feed`]) from the same back-end channel.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Url;
 use rocket::http::uri::Absolute;
-use rocket::uri;
+use rocket::http::{Accept, ContentType, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::{uri, FromFormField, Request, UriDisplayQuery};
 use rss::extension::itunes::{
     ITunesCategoryBuilder, ITunesChannelExtensionBuilder, ITunesItemExtensionBuilder,
 };
+use rss::extension::{Extension, ExtensionBuilder, ExtensionMap};
 use rss::{
     CategoryBuilder, ChannelBuilder, EnclosureBuilder, GuidBuilder, ImageBuilder, ItemBuilder,
 };
+use uuid::Uuid;
 
-use crate::backends::{Channel, Item};
+use crate::backends::{Channel, Chapter, Enclosure, Item};
+use crate::chapters;
+use crate::transcode::{self, MediaFormat};
+use crate::transcript::TranscriptFormat;
 use crate::Config;
 
+pub(crate) mod atom;
+pub(crate) mod json_feed;
+
+/// The prefix of the Podcasting 2.0 namespace.
+const PODCAST_PREFIX: &str = "podcast";
+
+/// The URL of the Podcasting 2.0 namespace.
+const PODCAST_NAMESPACE: &str = "https://podcastindex.org/namespace/1.0";
+
+/// The prefix of the Podlove Simple Chapters namespace.
+const PSC_PREFIX: &str = "psc";
+
+/// The URL of the Podlove Simple Chapters namespace.
+const PSC_NAMESPACE: &str = "http://podlove.org/simple-chapters";
+
+/// The version of Podlove Simple Chapters used.
+const PSC_VERSION: &str = "1.2";
+
+/// The UUID (v5) namespace used to derive the `podcast:guid` of a feed from its URL.
+const PODCAST_GUID_NAMESPACE: Uuid = Uuid::from_u128(0xead4c236_bf58_58c6_a2c6_a6b28d128cb6);
+
+/// The formats that feeds can be constructed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromFormField, UriDisplayQuery)]
+pub(crate) enum FeedFormat {
+    /// RSS 2.0, the default.
+    #[field(value = "rss")]
+    Rss,
+
+    /// Atom 1.0.
+    #[field(value = "atom")]
+    Atom,
+
+    /// JSON Feed 1.1.
+    #[field(value = "json")]
+    Json,
+}
+
+impl FeedFormat {
+    /// Splits the feed format off the provided channel ID if it has a feed format extension.
+    ///
+    /// Only the `.atom` and `.json` extensions are recognised. Channel IDs that are URLs (e.g. for
+    /// the RSS and yt-dlp back-ends) can end in any extension themselves, so they are never split;
+    /// the feed format of these can only be requested using the query or `Accept` header.
+    pub(crate) fn split_extension(channel_id: &str) -> (&str, Option<Self>) {
+        // Note that PeerTube playlist IDs (`playlist:id@host`) parse as URLs, but not HTTP(S) ones.
+        if Url::parse(channel_id).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return (channel_id, None);
+        }
+
+        [(".atom", FeedFormat::Atom), (".json", FeedFormat::Json)]
+            .into_iter()
+            .find_map(|(extension, feed_format)| {
+                let channel_id = channel_id.strip_suffix(extension)?;
+
+                Some((channel_id, Some(feed_format)))
+            })
+            .unwrap_or((channel_id, None))
+    }
+
+    /// Returns the feed format preferred by the provided `Accept` header (if any is supported).
+    pub(crate) fn negotiate(accept: &Accept) -> Option<Self> {
+        accept
+            .iter()
+            .filter(|media_type| media_type.weight_or(1.0) > 0.0)
+            .max_by(|media_type1, media_type2| {
+                media_type1
+                    .weight_or(1.0)
+                    .total_cmp(&media_type2.weight_or(1.0))
+            })
+            .and_then(
+                |media_type| match (media_type.top().as_str(), media_type.sub().as_str()) {
+                    ("application", "rss+xml" | "xml") | ("text", "xml") => Some(FeedFormat::Rss),
+                    ("application", "atom+xml") => Some(FeedFormat::Atom),
+                    ("application", "feed+json" | "json") => Some(FeedFormat::Json),
+                    _ => None,
+                },
+            )
+    }
+
+    /// Returns the content type of feeds in the format.
+    pub(crate) fn content_type(&self) -> ContentType {
+        match self {
+            // Keep using the generic XML type for RSS, which all podcast clients accept.
+            FeedFormat::Rss => ContentType::new("application", "xml"),
+            FeedFormat::Atom => ContentType::new("application", "atom+xml"),
+            FeedFormat::Json => ContentType::new("application", "feed+json"),
+        }
+    }
+}
+
+/// Renders a feed in the provided feed format from a back-end channel.
+///
+/// See [`construct`] for the other arguments.
+pub(crate) fn render(
+    feed_format: FeedFormat,
+    backend_id: &str,
+    channel_id: &str,
+    item_limit: Option<usize>,
+    config: &Config,
+    channel: Channel,
+    format: Option<MediaFormat>,
+) -> String {
+    match feed_format {
+        FeedFormat::Rss => {
+            construct(backend_id, channel_id, item_limit, config, channel, format).to_string()
+        }
+        FeedFormat::Atom => {
+            atom::construct(backend_id, channel_id, config, channel, format).to_string()
+        }
+        FeedFormat::Json => {
+            let feed = json_feed::construct(backend_id, channel_id, config, channel, format);
+
+            rocket::serde::json::to_string(&feed).expect("JSON feed can be serialized")
+        }
+    }
+}
+
+/// A rendered feed that is served supporting conditional requests.
+///
+/// The response carries an `ETag` (the SHA-1 hash of the content), a `Last-Modified` (the last
+/// build timestamp) and a `Cache-Control` header. Because the feed format can be negotiated, it
+/// also carries a `Vary: Accept` header so that shared caches keep the formats apart. If the
+/// `If-None-Match` or `If-Modified-Since` request header indicates that the client already has
+/// the feed, `304 Not Modified` is returned without the content.
+#[derive(Debug)]
+pub(crate) struct FeedResponse {
+    /// The content type of the feed.
+    content_type: ContentType,
+
+    /// The rendered feed.
+    content: String,
+
+    /// The entity tag of the feed (quoted).
+    etag: String,
+
+    /// The last build timestamp of the feed, if there are any items.
+    last_modified: Option<DateTime<Utc>>,
+
+    /// The duration clients are allowed to cache the feed (in seconds).
+    max_age: i64,
+}
+
+impl FeedResponse {
+    /// Creates a new feed response for a rendered feed.
+    ///
+    /// The last build timestamp is used for the `Last-Modified` header, unless it is the Unix epoch
+    /// (i.e. the feed has no items). The maximum age is used for the `Cache-Control` header.
+    pub(crate) fn new(
+        content_type: ContentType,
+        content: String,
+        last_build: DateTime<Utc>,
+        max_age: Duration,
+    ) -> Self {
+        let etag = format!("\"{}\"", sha1_smol::Sha1::from(&content).digest());
+        let last_modified = (last_build != DateTime::UNIX_EPOCH).then_some(last_build);
+
+        Self {
+            content_type,
+            content,
+            etag,
+            last_modified,
+            max_age: max_age.num_seconds().max(0),
+        }
+    }
+
+    /// Returns whether the client already has the feed according to the request headers.
+    ///
+    /// `If-None-Match` takes precedence over `If-Modified-Since` if both are present.
+    fn is_not_modified(&self, request: &Request<'_>) -> bool {
+        if let Some(if_none_match) = request.headers().get_one("If-None-Match") {
+            return if_none_match.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == self.etag
+            });
+        }
+
+        let if_modified_since = request
+            .headers()
+            .get_one("If-Modified-Since")
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+        match (if_modified_since, self.last_modified) {
+            (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for FeedResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut builder = Response::build();
+
+        builder
+            .raw_header("ETag", self.etag.clone())
+            .raw_header("Cache-Control", format!("public, max-age={}", self.max_age))
+            .raw_header("Vary", "Accept");
+        if let Some(last_modified) = self.last_modified {
+            let last_modified = last_modified.format("%a, %d %b %Y %H:%M:%S GMT");
+            builder.raw_header("Last-Modified", last_modified.to_string());
+        }
+        if self.is_not_modified(request) {
+            builder.status(Status::NotModified);
+        } else {
+            builder
+                .header(self.content_type)
+                .sized_body(self.content.len(), std::io::Cursor::new(self.content));
+        }
+
+        Ok(builder.finalize())
+    }
+}
+
 /// Constructs a feed as string from a back-end channel using the `rss` crate.
 ///
 /// It requires the backend and configuration to be able to construct download URLs.
-pub(crate) fn construct(backend_id: &str, config: &Config, channel: Channel) -> rss::Channel {
+/// The channel ID is required to construct the URL of the feed itself, and along with the item
+/// limit (if any) the URLs of the chapters of the items.
+/// If a format is provided, the enclosures will refer to media content transcoded to that format.
+pub(crate) fn construct(
+    backend_id: &str,
+    channel_id: &str,
+    item_limit: Option<usize>,
+    config: &Config,
+    channel: Channel,
+    format: Option<MediaFormat>,
+) -> rss::Channel {
     let category = CategoryBuilder::default()
         .name(channel.categories.first().cloned().unwrap_or_default())
         .build();
-    let mut last_build =
-        DateTime::from_timestamp(0, 0).expect("Out-of-range seconds or invalid nanoseconds");
-    let generator = String::from(concat!(
-        env!("CARGO_PKG_NAME"),
-        " ",
-        env!("CARGO_PKG_VERSION")
-    ));
+    let last_build = last_build(&channel.items);
+    let generator = generator();
     let image = channel
         .image
         .clone()
         .map(|url| ImageBuilder::default().link(url.clone()).url(url).build());
+    let podcast_ext = construct_podcast_ext(backend_id, channel_id, config, &channel);
     let items = channel
         .items
         .into_iter()
-        .map(|item| construct_item(backend_id, config, item, &mut last_build))
+        .map(|item| construct_item(backend_id, channel_id, item_limit, config, item, format))
         .collect::<Vec<_>>();
     let itunes_ext = ITunesChannelExtensionBuilder::default()
         .author(channel.author)
@@ -62,19 +291,159 @@ pub(crate) fn construct(backend_id: &str, config: &Config, channel: Channel) ->
         .image(image)
         .items(items)
         .itunes_ext(Some(itunes_ext))
+        .extensions(podcast_ext)
+        .namespaces(BTreeMap::from([
+            (
+                String::from(PODCAST_PREFIX),
+                String::from(PODCAST_NAMESPACE),
+            ),
+            (String::from(PSC_PREFIX), String::from(PSC_NAMESPACE)),
+        ]))
         .build()
 }
 
+/// Returns the URL of the feed for a back-end channel in the provided feed format (if any).
+fn feed_url(
+    backend_id: &str,
+    channel_id: &str,
+    config: &Config,
+    feed_format: Option<FeedFormat>,
+) -> String {
+    uri!(
+        Absolute::parse(&config.public_url).expect("valid URL"),
+        crate::get_feed(
+            backend_id = backend_id,
+            channel_id = channel_id,
+            limit = _,
+            format = _,
+            lang = _,
+            feed_format = feed_format
+        )
+    )
+    .to_string()
+}
+
+/// Returns the GUID of the feed for a back-end channel.
+///
+/// The GUID is derived from the URL of the (RSS) feed without scheme, as prescribed by the
+/// Podcasting 2.0 namespace specification for the `podcast:guid` tag.
+fn podcast_guid(backend_id: &str, channel_id: &str, config: &Config) -> Uuid {
+    let feed_url = feed_url(backend_id, channel_id, config, None);
+    let feed_url = feed_url
+        .split_once("://")
+        .map_or(feed_url.as_str(), |(_, rest)| rest)
+        .trim_end_matches('/');
+
+    Uuid::new_v5(&PODCAST_GUID_NAMESPACE, feed_url.as_bytes())
+}
+
+/// Returns the enclosure of a back-end item as published in a feed and its download URL.
+///
+/// If a format is provided, the enclosure will refer to media content transcoded to that format.
+fn published_enclosure(
+    backend_id: &str,
+    config: &Config,
+    enclosure: Enclosure,
+    duration: Option<u32>,
+    format: Option<MediaFormat>,
+) -> (Enclosure, String) {
+    // There is no need to transcode if the media content is already in the requested format.
+    let (enclosure, format) = match format {
+        Some(format) if enclosure.mime_type != format.mime_type() => {
+            let enclosure =
+                transcode::transcoded_enclosure(&config.transcode, enclosure, format, duration);
+
+            (enclosure, Some(format))
+        }
+        _ => (enclosure, None),
+    };
+    let url = uri!(
+        Absolute::parse(&config.public_url).expect("valid URL"),
+        crate::get_download(
+            backend_id = backend_id,
+            file = &enclosure.file,
+            format = format
+        )
+    );
+
+    (enclosure, url.to_string())
+}
+
+/// Returns the last build timestamp of a feed with the provided items.
+///
+/// This is the timestamp of the last update of the items, or the Unix epoch if there are none.
+pub(crate) fn last_build(items: &[Item]) -> DateTime<Utc> {
+    items
+        .iter()
+        .map(|item| item.updated_at)
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH)
+}
+
+/// Returns the generator of the feeds.
+fn generator() -> String {
+    String::from(concat!(
+        env!("CARGO_PKG_NAME"),
+        " ",
+        env!("CARGO_PKG_VERSION")
+    ))
+}
+
+/// Constructs the Podcasting 2.0 namespace extensions of the feed for a back-end channel.
+///
+/// Because Podbringer does not own the content, the feed is locked so
+/// that other platforms will not import it.
+fn construct_podcast_ext(
+    backend_id: &str,
+    channel_id: &str,
+    config: &Config,
+    channel: &Channel,
+) -> ExtensionMap {
+    let guid = podcast_guid(backend_id, channel_id, config);
+    let srcset = if channel.images.is_empty() {
+        channel.image.as_ref().map(ToString::to_string)
+    } else {
+        let srcset = channel
+            .images
+            .iter()
+            .map(|(width, url)| format!("{url} {width}w"))
+            .collect::<Vec<_>>();
+
+        Some(srcset.join(", "))
+    };
+
+    let mut podcast_ext = BTreeMap::new();
+    podcast_ext.insert("guid", podcast_element("guid", guid.to_string(), []));
+    podcast_ext.insert("locked", podcast_element("locked", String::from("yes"), []));
+    podcast_ext.insert(
+        "medium",
+        podcast_element("medium", String::from(channel.medium.as_str()), []),
+    );
+    if let Some(srcset) = srcset {
+        podcast_ext.insert(
+            "images",
+            podcast_element("images", None, [("srcset", srcset)]),
+        );
+    }
+    if let Some(author) = &channel.author {
+        podcast_ext.insert("person", podcast_element("person", author.clone(), []));
+    }
+
+    podcast_extension_map(podcast_ext)
+}
+
 /// Constructs an RSS feed item from a back-end item using the `rss` crate.
 ///
-/// It requires the backend and configuration to be able to construct download URLs.
-/// It also bumps the last build timestamp if the last updated timestamp is later than the current
-/// value.
+/// It requires the backend and configuration to be able to construct download URLs, and the
+/// channel ID and item limit (if any) to construct the URL of the chapters.
+/// If a format is provided, the enclosure will refer to media content transcoded to that format.
 fn construct_item(
     backend_id: &str,
+    channel_id: &str,
+    item_limit: Option<usize>,
     config: &Config,
     item: Item,
-    last_build: &mut DateTime<Utc>,
+    format: Option<MediaFormat>,
 ) -> rss::Item {
     let categories = item
         .categories
@@ -86,14 +455,14 @@ fn construct_item(
                 .build()
         })
         .collect::<Vec<_>>();
-    let url = uri!(
-        Absolute::parse(&config.public_url).expect("valid URL"),
-        crate::get_download(backend_id = backend_id, file = item.enclosure.file)
-    );
+    // The chapters refer to the original download file path, regardless of transcoding.
+    let chapters_file = item.enclosure.file.clone();
+    let (enclosure, url) =
+        published_enclosure(backend_id, config, item.enclosure, item.duration, format);
     let enclosure = EnclosureBuilder::default()
-        .url(url.to_string())
-        .length(item.enclosure.length.to_string())
-        .mime_type(item.enclosure.mime_type)
+        .url(url)
+        .length(enclosure.length.to_string())
+        .mime_type(enclosure.mime_type)
         .build();
     let guid = GuidBuilder::default()
         .value(item.guid)
@@ -105,10 +474,73 @@ fn construct_item(
         .duration(item.duration.map(|dur| format!("{dur}")))
         .subtitle(item.description.clone())
         .keywords(Some(keywords))
+        .season(item.season.map(|season| season.to_string()))
+        .episode(item.episode.map(|episode| episode.to_string()))
         .build();
+    let mut podcast_ext = BTreeMap::new();
+    if let Some(season) = item.season {
+        podcast_ext.insert("season", podcast_element("season", season.to_string(), []));
+    }
+    if let Some(episode) = item.episode {
+        podcast_ext.insert(
+            "episode",
+            podcast_element("episode", episode.to_string(), []),
+        );
+    }
+    if !item.chapters.is_empty() {
+        let chapters_url = uri!(
+            Absolute::parse(&config.public_url).expect("valid URL"),
+            crate::get_chapters(
+                backend_id = backend_id,
+                channel_id = channel_id,
+                file = chapters_file,
+                limit = item_limit
+            )
+        );
+        let attrs = [
+            ("url", chapters_url.to_string()),
+            ("type", String::from(chapters::MIME_TYPE)),
+        ];
+        podcast_ext.insert("chapters", podcast_element("chapters", None, attrs));
+    }
+    let mut extensions = podcast_extension_map(podcast_ext);
+    let transcripts = item
+        .transcripts
+        .iter()
+        .flat_map(|transcript| {
+            TranscriptFormat::ALL.map(|transcript_format| {
+                let transcript_url = uri!(
+                    Absolute::parse(&config.public_url).expect("valid URL"),
+                    crate::get_transcript(
+                        backend_id = backend_id,
+                        file = transcript
+                            .file
+                            .with_extension(transcript_format.extension())
+                    )
+                );
+                let attrs = [
+                    ("url", transcript_url.to_string()),
+                    ("type", String::from(transcript_format.mime_type())),
+                    ("language", transcript.language.clone()),
+                    ("rel", String::from("captions")),
+                ];
 
-    if item.updated_at > *last_build {
-        *last_build = item.updated_at;
+                podcast_element("transcript", None, attrs)
+            })
+        })
+        .collect::<Vec<_>>();
+    if !transcripts.is_empty() {
+        extensions
+            .entry(String::from(PODCAST_PREFIX))
+            .or_default()
+            .insert(String::from("transcript"), transcripts);
+    }
+    if !item.chapters.is_empty() {
+        let psc_chapters = BTreeMap::from([(
+            String::from("chapters"),
+            Vec::from([psc_chapters(&item.chapters)]),
+        )]);
+        extensions.insert(String::from(PSC_PREFIX), psc_chapters);
     }
 
     ItemBuilder::default()
@@ -120,5 +552,92 @@ fn construct_item(
         .guid(Some(guid))
         .pub_date(Some(item.published_at.to_rfc2822()))
         .itunes_ext(Some(itunes_ext))
+        .extensions(extensions)
+        .build()
+}
+
+/// Constructs a Podcasting 2.0 namespace element with the provided value and attributes.
+fn podcast_element<const N: usize>(
+    name: &str,
+    value: impl Into<Option<String>>,
+    attrs: [(&str, String); N],
+) -> Extension {
+    ExtensionBuilder::default()
+        .name(format!("{PODCAST_PREFIX}:{name}"))
+        .value(value.into())
+        .attrs(
+            attrs
+                .into_iter()
+                .map(|(key, value)| (String::from(key), value))
+                .collect::<BTreeMap<_, _>>(),
+        )
         .build()
 }
+
+/// Constructs the Podlove Simple Chapters element for the provided chapters.
+fn psc_chapters(chapters: &[Chapter]) -> Extension {
+    let chapters = chapters
+        .iter()
+        .map(|chapter| {
+            let start = chapter.start_time;
+            let start = format!(
+                "{:02}:{:02}:{:02}",
+                start / 3600,
+                start / 60 % 60,
+                start % 60
+            );
+
+            ExtensionBuilder::default()
+                .name(format!("{PSC_PREFIX}:chapter"))
+                .attrs(BTreeMap::from([
+                    (String::from("start"), start),
+                    (String::from("title"), chapter.title.clone()),
+                ]))
+                .build()
+        })
+        .collect();
+
+    ExtensionBuilder::default()
+        .name(format!("{PSC_PREFIX}:chapters"))
+        .attrs(BTreeMap::from([(
+            String::from("version"),
+            String::from(PSC_VERSION),
+        )]))
+        .children(BTreeMap::from([(String::from("chapter"), chapters)]))
+        .build()
+}
+
+/// Constructs an extension map for the Podcasting 2.0 namespace from elements by their name.
+fn podcast_extension_map(elements: BTreeMap<&str, Extension>) -> ExtensionMap {
+    let elements = elements
+        .into_iter()
+        .map(|(name, element)| (String::from(name), Vec::from([element])))
+        .collect();
+
+    BTreeMap::from([(String::from(PODCAST_PREFIX), elements)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_extension() {
+        assert_eq!(
+            FeedFormat::split_extension("myfavouriteband.json"),
+            ("myfavouriteband", Some(FeedFormat::Json))
+        );
+        assert_eq!(
+            FeedFormat::split_extension("playlist:id@peertube.example.org.atom"),
+            ("playlist:id@peertube.example.org", Some(FeedFormat::Atom))
+        );
+        assert_eq!(
+            FeedFormat::split_extension("myfavouriteband"),
+            ("myfavouriteband", None)
+        );
+        assert_eq!(
+            FeedFormat::split_extension("https://example.org/feed.json"),
+            ("https://example.org/feed.json", None)
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Helper functions for constructing Atom feeds.
+
+use atom_syndication::{
+    CategoryBuilder, ContentBuilder, EntryBuilder, FeedBuilder, GeneratorBuilder, LinkBuilder,
+    PersonBuilder, Text,
+};
+use uuid::Uuid;
+
+use super::{feed_url, generator, last_build, podcast_guid, published_enclosure, FeedFormat};
+use crate::backends::{Channel, Item};
+use crate::transcode::MediaFormat;
+use crate::Config;
+
+/// Constructs an Atom feed from a back-end channel using the `atom_syndication` crate.
+///
+/// See [`super::construct`] for the arguments. The IDs of the feed and its entries are URNs
+/// derived from the GUID of the feed (see [`podcast_guid`]).
+pub(crate) fn construct(
+    backend_id: &str,
+    channel_id: &str,
+    config: &Config,
+    channel: Channel,
+    format: Option<MediaFormat>,
+) -> atom_syndication::Feed {
+    let guid = podcast_guid(backend_id, channel_id, config);
+    let updated = last_build(&channel.items);
+    let links = [
+        LinkBuilder::default()
+            .href(channel.link.to_string())
+            .rel("alternate")
+            .build(),
+        LinkBuilder::default()
+            .href(feed_url(
+                backend_id,
+                channel_id,
+                config,
+                Some(FeedFormat::Atom),
+            ))
+            .rel("self")
+            .mime_type(Some(String::from("application/atom+xml")))
+            .build(),
+    ];
+    // An Atom feed requires an author, so fall back to the title of the channel.
+    let author = PersonBuilder::default()
+        .name(channel.author.unwrap_or_else(|| channel.title.clone()))
+        .build();
+    let categories = channel
+        .categories
+        .into_iter()
+        .map(|cat| CategoryBuilder::default().term(cat).build())
+        .collect::<Vec<_>>();
+    let generator = GeneratorBuilder::default().value(generator()).build();
+    let entries = channel
+        .items
+        .into_iter()
+        .map(|item| construct_entry(backend_id, config, &guid, item, format))
+        .collect::<Vec<_>>();
+
+    FeedBuilder::default()
+        .title(channel.title)
+        .id(format!("urn:uuid:{guid}"))
+        .updated(updated)
+        .authors([author])
+        .categories(categories)
+        .generator(Some(generator))
+        .icon(channel.image.as_ref().map(ToString::to_string))
+        .logo(channel.image.map(String::from))
+        .links(links)
+        .subtitle(Some(Text::plain(channel.description)))
+        .entries(entries)
+        .build()
+}
+
+/// Constructs an Atom feed entry from a back-end item using the `atom_syndication` crate.
+///
+/// The ID of the entry is derived from the feed GUID and the item GUID.
+fn construct_entry(
+    backend_id: &str,
+    config: &Config,
+    feed_guid: &Uuid,
+    item: Item,
+    format: Option<MediaFormat>,
+) -> atom_syndication::Entry {
+    let id = Uuid::new_v5(feed_guid, item.guid.as_bytes());
+    let (enclosure, url) =
+        published_enclosure(backend_id, config, item.enclosure, item.duration, format);
+    let links = [
+        LinkBuilder::default()
+            .href(item.link.to_string())
+            .rel("alternate")
+            .build(),
+        LinkBuilder::default()
+            .href(url)
+            .rel("enclosure")
+            .mime_type(Some(enclosure.mime_type))
+            .length(Some(enclosure.length.to_string()))
+            .build(),
+    ];
+    let categories = item
+        .categories
+        .into_iter()
+        .map(|(cat_name, cat_url)| {
+            CategoryBuilder::default()
+                .term(cat_name)
+                .scheme(Some(cat_url.to_string()))
+                .build()
+        })
+        .collect::<Vec<_>>();
+    let content = item.description.map(|description| {
+        ContentBuilder::default()
+            .value(Some(description))
+            .content_type(Some(String::from("text")))
+            .build()
+    });
+
+    EntryBuilder::default()
+        .title(item.title)
+        .id(format!("urn:uuid:{id}"))
+        .updated(item.updated_at)
+        .published(Some(item.published_at.into()))
+        .links(links)
+        .categories(categories)
+        .content(content)
+        .build()
+}
@@ -0,0 +1,168 @@
+//! Helper functions for constructing JSON feeds.
+//!
+//! See also: <https://www.jsonfeed.org/version/1.1/>
+
+use chrono::{DateTime, Utc};
+use rocket::serde::Serialize;
+
+use super::{feed_url, published_enclosure, FeedFormat};
+use crate::backends::{Channel, Item};
+use crate::transcode::MediaFormat;
+use crate::Config;
+
+/// The version URL of the JSON Feed format.
+const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// A JSON feed.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct JsonFeed {
+    /// The version URL of the JSON Feed format.
+    version: &'static str,
+
+    /// The title of the feed.
+    title: String,
+
+    /// The URL of the website the feed is for.
+    home_page_url: String,
+
+    /// The URL of the feed itself.
+    feed_url: String,
+
+    /// The description of the feed.
+    description: String,
+
+    /// The URL of the image of the feed (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+
+    /// The authors of the feed.
+    authors: Vec<Author>,
+
+    /// The items of the feed.
+    items: Vec<JsonItem>,
+}
+
+/// A JSON feed author.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Author {
+    /// The name of the author.
+    name: String,
+}
+
+/// A JSON feed item.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct JsonItem {
+    /// The unique ID of the item.
+    id: String,
+
+    /// The URL of the item.
+    url: String,
+
+    /// The title of the item.
+    title: String,
+
+    /// The (plain text) content of the item.
+    content_text: String,
+
+    /// The URL of the image of the item (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+
+    /// The timestamp the item was published.
+    date_published: DateTime<Utc>,
+
+    /// The timestamp the item was last modified.
+    date_modified: DateTime<Utc>,
+
+    /// The tags (keywords and categories) of the item.
+    tags: Vec<String>,
+
+    /// The attachments (enclosed media content) of the item.
+    attachments: Vec<Attachment>,
+}
+
+/// A JSON feed item attachment.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Attachment {
+    /// The download URL of the attachment.
+    url: String,
+
+    /// The MIME type of the attachment.
+    mime_type: String,
+
+    /// The size of the attachment (in bytes).
+    size_in_bytes: u64,
+
+    /// The duration of the attachment (in seconds, if known).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_in_seconds: Option<u32>,
+}
+
+/// Constructs a JSON feed from a back-end channel.
+///
+/// See [`super::construct`] for the arguments.
+pub(crate) fn construct(
+    backend_id: &str,
+    channel_id: &str,
+    config: &Config,
+    channel: Channel,
+    format: Option<MediaFormat>,
+) -> JsonFeed {
+    let authors = channel
+        .author
+        .map(|name| Vec::from([Author { name }]))
+        .unwrap_or_default();
+    let items = channel
+        .items
+        .into_iter()
+        .map(|item| construct_item(backend_id, config, item, format))
+        .collect();
+
+    JsonFeed {
+        version: VERSION,
+        title: channel.title,
+        home_page_url: channel.link.to_string(),
+        feed_url: feed_url(backend_id, channel_id, config, Some(FeedFormat::Json)),
+        description: channel.description,
+        icon: channel.image.map(String::from),
+        authors,
+        items,
+    }
+}
+
+/// Constructs a JSON feed item from a back-end item.
+fn construct_item(
+    backend_id: &str,
+    config: &Config,
+    item: Item,
+    format: Option<MediaFormat>,
+) -> JsonItem {
+    let (enclosure, url) =
+        published_enclosure(backend_id, config, item.enclosure, item.duration, format);
+    let attachment = Attachment {
+        url,
+        mime_type: enclosure.mime_type,
+        size_in_bytes: enclosure.length,
+        duration_in_seconds: item.duration,
+    };
+    let mut tags = item.keywords;
+    tags.extend(item.categories.into_keys());
+    tags.sort();
+    tags.dedup();
+
+    JsonItem {
+        id: item.guid,
+        url: item.link.to_string(),
+        title: item.title,
+        content_text: item.description.unwrap_or_default(),
+        image: item.image.map(String::from),
+        date_published: item.published_at,
+        date_modified: item.updated_at,
+        tags,
+        attachments: Vec::from([attachment]),
+    }
+}
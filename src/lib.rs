@@ -14,34 +14,122 @@
 )]
 #![deny(missing_docs)]
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+use reqwest::Url;
 use rocket::fairing::AdHoc;
-use rocket::http::Status;
+use rocket::http::uri::Absolute;
+use rocket::http::{Accept, ContentType, Status};
 use rocket::response::Redirect;
+use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
-use rocket::{get, routes, Build, Request, Responder, Rocket, State};
+use rocket::{get, routes, uri, Build, Request, Rocket, State};
 use rocket_dyn_templates::{context, Template};
 
-use crate::backends::Backend;
+use crate::archive::{Archive, ArchiveConfig};
+use crate::backends::local::LocalConfig;
+use crate::cache::CacheConfig;
+use crate::download::{Download, DownloadMode, RangeHeader};
+use crate::feed::{FeedFormat, FeedResponse};
+use crate::refresh::{RefreshConfig, Subscriptions};
+use crate::transcode::{MediaFormat, TranscodeConfig, TranscodeLimit};
+use crate::transcript::TranscriptFormat;
 
+pub(crate) mod archive;
 pub(crate) mod backends;
+pub(crate) mod cache;
+pub(crate) mod chapters;
+pub(crate) mod download;
 pub(crate) mod feed;
+pub(crate) mod refresh;
+pub(crate) mod transcode;
+pub(crate) mod transcript;
+
+pub use crate::backends::{
+    Backend, BackendConfig, BackendContext, BackendInfo, Channel, Chapter, Enclosure, Item,
+    LocalContent, Medium, Registry, Transcript,
+};
+pub use crate::cache::{Cache, CacheKind, CacheTtls};
 
 /// The possible errors that can occur.
 #[derive(Debug, thiserror::Error)]
-pub(crate) enum Error {
+pub enum Error {
+    /// A cache store error occurred.
+    #[error("Cache store error: {0}")]
+    Cache(LibraryError),
+
+    /// The SoundCloud client ID was rejected by the API.
+    #[error("SoundCloud client ID rejected")]
+    ClientIdRejected,
+
+    /// Disabled back-end encountered.
+    #[error("Disabled back-end: {0}")]
+    DisabledBackend(String),
+
+    /// An upstream feed exceeds the maximum size.
+    #[error("Feed too large: {0}")]
+    FeedTooLarge(String),
+
     /// A standard I/O error occurred.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// A JSON (de)serialization error occurred.
+    #[error("JSON (de)serialization error: {0}")]
+    Json(LibraryError),
+
+    /// No base URL configured for a back-end that requires one.
+    #[error("No base URL configured for back-end: {0}")]
+    MissingBaseUrl(String),
+
+    /// No chapters found for the media content of an item.
+    #[error("No chapters found")]
+    NoChaptersFound,
+
+    /// No SoundCloud client ID found on the website.
+    #[error("No SoundCloud client ID found")]
+    NoClientIdFound,
+
+    /// No streams found for any of the videos of a channel or playlist.
+    #[error("No streams found for any of the videos")]
+    NoStreamsFound,
+
+    /// No transcript found for the media content of an item.
+    #[error("No transcript found")]
+    NoTranscriptFound,
+
     /// No redirect URL found in item metadata.
     #[error("No redirect URL found")]
     NoRedirectUrlFound,
 
+    /// The requested range cannot be satisfied.
+    #[error("Requested range not satisfiable")]
+    RangeNotSatisfiable,
+
     /// A (reqwest) HTTP error occurred.
     #[error("HTTP error: {0}")]
-    Request(#[from] reqwest::Error),
+    Request(LibraryError),
+
+    /// An RSS feed parse error occurred.
+    #[error("RSS parse error: {0}")]
+    Rss(LibraryError),
+
+    /// Too many media content transcodings are running already.
+    #[error("Too many transcodings running")]
+    TranscodeBusy,
+
+    /// Transcoding is not enabled for a back-end.
+    #[error("Transcoding to {0} not enabled for back-end: {1}")]
+    TranscodeDisabled(String, String),
+
+    /// Transcoding failed to start.
+    #[error("Transcoding failed to start")]
+    TranscodeFailed,
+
+    /// Unsupported channel encountered.
+    #[error("Unsupported channel: {0}")]
+    UnsupportedChannel(String),
 
     /// Unsupported back-end encountered.
     #[error("Unsupported back-end: {0}")]
@@ -49,31 +137,95 @@ pub(crate) enum Error {
 
     /// A URL parse error occurred.
     #[error("URL parse error: {0}")]
-    UrlParse(#[from] url::ParseError),
+    UrlParse(LibraryError),
 
     /// An error occurred in youtube-dl.
     #[error("Youtube-dl failed: {0}")]
-    YoutubeDl(#[from] youtube_dl::Error),
+    YoutubeDl(LibraryError),
 
-    /// An YouTube extract error occurred.
+    /// An YouTube extract (including ID parsing and playlist video) error occurred.
     #[error("YouTube extract error: {0}")]
-    YtExtract(#[from] ytextract::Error),
+    YtExtract(LibraryError),
+}
 
-    /// An YouTube extract ID parsing error occurred.
-    #[error("YouTube extract ID parsing error: {0}")]
-    YtExtractId0(#[from] ytextract::error::Id<0>),
+/// An error that occurred in one of the underlying libraries.
+///
+/// The error is opaque so that the types of the underlying libraries are not part of the public
+/// API; it can be displayed and its source can be inspected.
+#[derive(Debug)]
+pub struct LibraryError(Box<dyn std::error::Error + Send + Sync>);
 
-    /// An YouTube extract ID parsing error occurred.
-    #[error("YouTube extract ID parsing error: {0}")]
-    YtExtractId11(#[from] ytextract::error::Id<11>),
+impl LibraryError {
+    /// Wraps the provided error of an underlying library.
+    fn new(err: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self(Box::new(err))
+    }
+}
 
-    /// An YouTube extract ID parsing error occurred.
-    #[error("YouTube extract ID parsing error: {0}")]
-    YtExtractId24(#[from] ytextract::error::Id<24>),
+impl std::fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
-    /// An YouTube extract playlist video error occurred.
-    #[error("YouTube extract playlist video error: {0}")]
-    YtExtractPlaylistVideo(#[from] ytextract::playlist::video::Error),
+impl std::error::Error for LibraryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl From<sled::Error> for Error {
+    fn from(err: sled::Error) -> Self {
+        Error::Cache(LibraryError::new(err))
+    }
+}
+
+impl From<rocket::serde::json::serde_json::Error> for Error {
+    fn from(err: rocket::serde::json::serde_json::Error) -> Self {
+        Error::Json(LibraryError::new(err))
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Request(LibraryError::new(err))
+    }
+}
+
+impl From<rss::Error> for Error {
+    fn from(err: rss::Error) -> Self {
+        Error::Rss(LibraryError::new(err))
+    }
+}
+
+impl From<url::ParseError> for Error {
+    fn from(err: url::ParseError) -> Self {
+        Error::UrlParse(LibraryError::new(err))
+    }
+}
+
+impl From<youtube_dl::Error> for Error {
+    fn from(err: youtube_dl::Error) -> Self {
+        Error::YoutubeDl(LibraryError::new(err))
+    }
+}
+
+impl From<ytextract::Error> for Error {
+    fn from(err: ytextract::Error) -> Self {
+        Error::YtExtract(LibraryError::new(err))
+    }
+}
+
+impl<const N: usize> From<ytextract::error::Id<N>> for Error {
+    fn from(err: ytextract::error::Id<N>) -> Self {
+        Error::YtExtract(LibraryError::new(err))
+    }
+}
+
+impl From<ytextract::playlist::video::Error> for Error {
+    fn from(err: ytextract::playlist::video::Error) -> Self {
+        Error::YtExtract(LibraryError::new(err))
+    }
 }
 
 impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Error {
@@ -81,64 +233,406 @@ impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Error {
         eprintln!("💥 Encountered error: {self}");
 
         match self {
-            Error::NoRedirectUrlFound => Err(Status::NotFound),
+            Error::DisabledBackend(_)
+            | Error::NoChaptersFound
+            | Error::NoRedirectUrlFound
+            | Error::NoTranscriptFound
+            | Error::TranscodeDisabled(_, _)
+            | Error::UnsupportedBackend(_)
+            | Error::UnsupportedChannel(_) => Err(Status::NotFound),
+            Error::RangeNotSatisfiable => Err(Status::RangeNotSatisfiable),
+            Error::TranscodeBusy => Err(Status::ServiceUnavailable),
             _ => Err(Status::InternalServerError),
         }
     }
 }
 
 /// Result type that defaults to [`Error`] as the default error type.
-pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// The extra application specific configuration.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub(crate) struct Config {
     /// The public URL at which the application is hosted or proxied from.
     #[serde(default)]
     public_url: String,
+
+    /// The configuration of the (persistent) cache.
+    #[serde(default)]
+    cache: CacheConfig,
+
+    /// The configuration of refreshing feeds in the background.
+    #[serde(default)]
+    refresh: RefreshConfig,
+
+    /// The configuration of transcoding media content.
+    #[serde(default)]
+    transcode: TranscodeConfig,
+
+    /// The configuration of the offline media archive.
+    #[serde(default)]
+    archive: ArchiveConfig,
+
+    /// The configuration of the local directories that are published.
+    #[serde(default)]
+    local: LocalConfig,
+
+    /// The configuration per back-end (by ID).
+    #[serde(default)]
+    backends: HashMap<String, BackendConfig>,
 }
 
-/// A Rocket responder wrapper type for RSS feeds.
-#[derive(Responder)]
-#[response(content_type = "application/xml")]
-struct RssFeed(String);
+impl Config {
+    /// Returns the configuration for the back-end with the provided ID.
+    ///
+    /// If the back-end is not configured, the default configuration is returned.
+    pub(crate) fn backend(&self, backend_id: &str) -> BackendConfig {
+        self.backends.get(backend_id).cloned().unwrap_or_default()
+    }
+}
 
-/// Retrieves a download by redirecting to the URL resolved by the selected back-end.
-#[get("/download/<backend_id>/<file..>")]
-pub(crate) async fn get_download(file: PathBuf, backend_id: &str) -> Result<Redirect> {
-    let backend = backends::get(backend_id)?;
+/// Retrieves a download from the archive, the back-end or the URL resolved by the back-end.
+///
+/// If the download has been archived, it is served from the archive. If the back-end has the
+/// media content available locally, it is served directly. If a format is provided,
+/// the media content is transcoded to that format, but only if it is the format configured for
+/// the back-end. Otherwise, depending on the download mode
+/// configured for the back-end, this either redirects to the URL or proxies the media content
+/// (including the requested range, if any).
+#[get("/download/<backend_id>/<file..>?<format>")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get_download(
+    file: PathBuf,
+    backend_id: &str,
+    format: Option<MediaFormat>,
+    range: RangeHeader,
+    registry: &State<Registry>,
+    config: &State<Config>,
+    cache: &State<Cache>,
+    archive: &State<Archive>,
+    transcode_limit: &State<TranscodeLimit>,
+) -> Result<Download> {
+    // Look up the back-end first, so that nothing is served for disabled back-ends, not even
+    // archived files.
+    let backend = registry.get(backend_id, config, cache)?;
+    check_transcode_enabled(
+        format,
+        default_format(config, backend_id, &*backend),
+        backend_id,
+    )?;
+    if let Some(path) = archive.local_file(backend_id, &file).await {
+        return get_local_download(&path, format, range, config, transcode_limit).await;
+    }
 
-    backend.redirect_url(&file).await.map(Redirect::to)
+    match backend.local_content(&file).await? {
+        Some(LocalContent::File(path)) => {
+            return get_local_download(&path, format, range, config, transcode_limit).await;
+        }
+        Some(LocalContent::Data { mime_type, data }) => {
+            let content_type = ContentType::parse_flexible(&mime_type).unwrap_or_default();
+            return Ok(Download::Data((content_type, data)));
+        }
+        None => {}
+    }
+    let url = backend.redirect_url(&file).await?;
+
+    if let Some(format) = format {
+        return transcode::transcode(&config.transcode, transcode_limit, &url, format)
+            .map(Download::Transcode);
+    }
+    let settings = config.backend(backend_id);
+    match settings.download {
+        DownloadMode::Redirect => Ok(Download::Redirect(Redirect::to(url))),
+        DownloadMode::Proxy => download::proxy(&settings.http_client(), &url, range)
+            .await
+            .map(Download::Proxy),
+    }
+}
+
+/// Returns the format the media content of a back-end is transcoded to by default (if any).
+///
+/// This is the format configured for the back-end, or MP3 if the back-end requires transcoding.
+fn default_format(config: &Config, backend_id: &str, backend: &dyn Backend) -> Option<MediaFormat> {
+    config
+        .backend(backend_id)
+        .format
+        .or_else(|| backend.requires_transcoding().then_some(MediaFormat::Mp3))
+}
+
+/// Checks whether transcoding to the provided format (if any) is enabled for a back-end.
+///
+/// Transcoding is only enabled for the default format of the back-end (see [`default_format`]).
+fn check_transcode_enabled(
+    format: Option<MediaFormat>,
+    default_format: Option<MediaFormat>,
+    backend_id: &str,
+) -> Result<()> {
+    match format {
+        Some(format) if default_format != Some(format) => Err(Error::TranscodeDisabled(
+            format.extension().to_string(),
+            backend_id.to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Serves a download from a local file.
+///
+/// If a format is provided, the media content is transcoded to that format.
+async fn get_local_download(
+    path: &Path,
+    format: Option<MediaFormat>,
+    range: RangeHeader,
+    config: &Config,
+    transcode_limit: &TranscodeLimit,
+) -> Result<Download> {
+    match format {
+        Some(format) => {
+            let input = path.to_string_lossy();
+            transcode::transcode(&config.transcode, transcode_limit, &input, format)
+                .map(Download::Transcode)
+        }
+        None => download::local(path, range).await.map(Download::Local),
+    }
 }
 
 /// Handler for retrieving the RSS feed of a channel on a certain back-end.
 ///
 /// The limit parameter determines the maximum of items that can be in the feed.
-#[get("/feed/<backend_id>/<channel_id>?<limit>")]
+/// The format parameter determines the format the media content of the items is transcoded to;
+/// it can only be the default format of the back-end, which is also used if it is not provided.
+/// The lang parameter determines the (comma-separated) languages of the transcripts that are
+/// included; if not provided, the transcript languages configured for the back-end are used.
+/// The feed format (RSS, Atom or JSON Feed) is determined by the extension of the channel ID
+/// (`.atom` or `.json`), the feed format parameter or the `Accept` header, in that order; it is
+/// RSS by default.
+/// The feed is recorded as a subscription so that it will be refreshed in the background.
+/// Conditional requests are supported using the ETag and last build timestamp of the feed, and
+/// clients are allowed to cache it for the time-to-live of the items of the back-end.
+/// If archiving is enabled for the back-end, the items are archived in the background and the
+/// archived items are merged into the feed.
+#[get("/feed/<backend_id>/<channel_id>?<limit>&<format>&<lang>&<feed_format>")]
+#[allow(clippy::too_many_arguments)]
 async fn get_feed(
     backend_id: &str,
     channel_id: &str,
     limit: Option<usize>,
+    format: Option<MediaFormat>,
+    lang: Option<&str>,
+    feed_format: Option<FeedFormat>,
+    accept: Option<&Accept>,
+    registry: &State<Registry>,
     config: &State<Config>,
-) -> Result<RssFeed> {
-    let backend = backends::get(backend_id)?;
-    let channel = backend.channel(channel_id, limit).await?;
-    let feed = feed::construct(backend_id, config, channel);
+    cache: &State<Cache>,
+    archive: &State<Archive>,
+    subscriptions: &State<Subscriptions>,
+) -> Result<FeedResponse> {
+    let (channel_id, extension_feed_format) = FeedFormat::split_extension(channel_id);
+    let feed_format = extension_feed_format
+        .or(feed_format)
+        .or_else(|| accept.and_then(FeedFormat::negotiate))
+        .unwrap_or(FeedFormat::Rss);
+    let backend = registry.get(backend_id, config, cache)?;
+    let default_format = default_format(config, backend_id, &*backend);
+    check_transcode_enabled(format, default_format, backend_id)?;
+    let mut channel = backend.channel(channel_id, limit).await?;
+    subscriptions.record(backend_id, channel_id, limit);
+    let client = config.backend(backend_id).http_client();
+    archive.update(backend, client, backend_id, channel_id, &channel.items);
+    archive
+        .merge(backend_id, channel_id, &mut channel, limit)
+        .await?;
+    let languages = match lang {
+        Some(lang) => lang.split(',').map(String::from).collect(),
+        None => config.backend(backend_id).transcript_languages,
+    };
+    transcript::retain_languages(&mut channel.items, &languages);
+    let format = format.or(default_format);
+    let last_build = feed::last_build(&channel.items);
+    let max_age = config.backend(backend_id).ttl.get(CacheKind::Items);
+    let feed = feed::render(
+        feed_format,
+        backend_id,
+        channel_id,
+        limit,
+        config,
+        channel,
+        format,
+    );
 
-    Ok(RssFeed(feed.to_string()))
+    Ok(FeedResponse::new(
+        feed_format.content_type(),
+        feed,
+        last_build,
+        max_age,
+    ))
+}
+
+/// Handler for retrieving the chapters of the media content of an item.
+///
+/// The chapters are returned as a JSON chapters document. They are looked up in the items of the
+/// channel (up to the limit of the feed) that contains the item, including the archived ones.
+#[get("/chapters/<backend_id>/<channel_id>/<file..>?<limit>")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get_chapters(
+    backend_id: &str,
+    channel_id: &str,
+    file: PathBuf,
+    limit: Option<usize>,
+    registry: &State<Registry>,
+    config: &State<Config>,
+    cache: &State<Cache>,
+    archive: &State<Archive>,
+) -> Result<Json<chapters::Document>> {
+    let backend = registry.get(backend_id, config, cache)?;
+    let mut channel = backend.channel(channel_id, limit).await?;
+    archive
+        .merge(backend_id, channel_id, &mut channel, limit)
+        .await?;
+    let document = chapters::lookup(channel.items, &file).ok_or(Error::NoChaptersFound)?;
+
+    Ok(Json(document))
+}
+
+/// Handler for retrieving a transcript of the media content of an item.
+///
+/// The back-end provides the transcript in WebVTT format; it is converted to SRT if the transcript
+/// file path has the `srt` extension.
+#[get("/transcript/<backend_id>/<file..>")]
+pub(crate) async fn get_transcript(
+    backend_id: &str,
+    file: PathBuf,
+    registry: &State<Registry>,
+    config: &State<Config>,
+    cache: &State<Cache>,
+) -> Result<(ContentType, String)> {
+    let format = TranscriptFormat::from_path(&file).ok_or(Error::NoTranscriptFound)?;
+    let backend = registry.get(backend_id, config, cache)?;
+    let vtt_file = file.with_extension(TranscriptFormat::Vtt.extension());
+    let vtt = backend
+        .transcript(&vtt_file)
+        .await?
+        .ok_or(Error::NoTranscriptFound)?;
+    let content_type = ContentType::parse_flexible(format.mime_type()).unwrap_or(ContentType::Text);
+
+    Ok((content_type, format.convert(vtt)))
+}
+
+/// Resolves the provided URL of a service to the corresponding feed and redirects to it.
+///
+/// The URL can be the URL of a channel, user, playlist, album, etc. of one of the enabled
+/// back-ends. If it has no scheme, `https://` is assumed.
+#[get("/resolve?<url>")]
+async fn get_resolve(
+    url: &str,
+    registry: &State<Registry>,
+    config: &State<Config>,
+    cache: &State<Cache>,
+) -> Result<Redirect> {
+    let service_url = Url::parse(url).or_else(|_| Url::parse(&format!("https://{url}")))?;
+    let (backend_id, channel_id) = registry
+        .resolve(&service_url, config, cache)
+        .await?
+        .ok_or_else(|| Error::UnsupportedChannel(url.to_string()))?;
+    let feed_url = uri!(
+        Absolute::parse(&config.public_url).expect("valid URL"),
+        get_feed(
+            backend_id = backend_id,
+            channel_id = channel_id,
+            limit = _,
+            format = _,
+            lang = _,
+            feed_format = _
+        )
+    );
+
+    Ok(Redirect::to(feed_url.to_string()))
 }
 
 /// Returns a simple index page that explains the usage.
+///
+/// The supported services are the enabled back-ends in the registry.
 #[get("/")]
-pub(crate) async fn get_index(config: &State<Config>) -> Template {
-    Template::render("index", context! { url: &config.public_url })
+pub(crate) async fn get_index(registry: &State<Registry>, config: &State<Config>) -> Template {
+    let backends = registry.enabled_backends(config).collect::<Vec<_>>();
+
+    Template::render(
+        "index",
+        context! { url: &config.public_url, backends: backends },
+    )
 }
 
-/// Sets up Rocket.
+/// Sets up Rocket with the built-in back-ends.
 pub fn setup() -> Rocket<Build> {
+    setup_with(Registry::with_builtins())
+}
+
+/// Sets up Rocket with the back-ends in the provided registry.
+///
+/// This can be used when embedding Podbringer to register additional back-ends.
+pub fn setup_with(registry: Registry) -> Rocket<Build> {
     rocket::build()
-        .mount("/", routes![get_download, get_feed, get_index])
+        .mount(
+            "/",
+            routes![
+                get_chapters,
+                get_download,
+                get_feed,
+                get_index,
+                get_resolve,
+                get_transcript
+            ],
+        )
+        .manage(registry)
         .attach(AdHoc::config::<Config>())
+        .attach(AdHoc::try_on_ignite("Cache", |rocket| async {
+            let config = rocket.state::<Config>().expect("configuration is loaded");
+
+            match Cache::open(&config.cache) {
+                Ok(cache) => Ok(rocket.manage(cache)),
+                Err(err) => {
+                    eprintln!("💥 Could not open cache store: {err}");
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_ignite("Subscriptions", |rocket| async {
+            let config = rocket.state::<Config>().expect("configuration is loaded");
+            let subscriptions = Subscriptions::new(&config.refresh);
+
+            rocket.manage(subscriptions)
+        }))
+        .attach(AdHoc::on_ignite("Transcode limit", |rocket| async {
+            let config = rocket.state::<Config>().expect("configuration is loaded");
+            let transcode_limit = TranscodeLimit::new(&config.transcode);
+
+            rocket.manage(transcode_limit)
+        }))
+        .attach(AdHoc::on_ignite("Archive", |rocket| async {
+            let config = rocket.state::<Config>().expect("configuration is loaded");
+            let archive = Archive::new(config);
+
+            rocket.manage(archive)
+        }))
+        .attach(AdHoc::on_liftoff("Refresher", |rocket| {
+            Box::pin(async move {
+                let registry = rocket.state::<Registry>().expect("registry is managed");
+                let config = rocket.state::<Config>().expect("configuration is loaded");
+                let cache = rocket.state::<Cache>().expect("cache is opened");
+                let archive = rocket.state::<Archive>().expect("archive is managed");
+                let subscriptions = rocket
+                    .state::<Subscriptions>()
+                    .expect("subscriptions are managed");
+
+                tokio::spawn(refresh::run(
+                    registry.clone(),
+                    config.clone(),
+                    cache.clone(),
+                    archive.clone(),
+                    subscriptions.clone(),
+                ));
+            })
+        }))
         .attach(Template::fairing())
 }
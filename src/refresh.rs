@@ -0,0 +1,205 @@
+//! Background refreshing of recently requested feeds.
+//!
+//! Every feed that is requested is remembered as a subscription, up to a maximum number. A
+//! background task periodically refreshes the cached channels and items of all subscriptions that
+//! are about to expire, a few at a time, so that podcast clients never have to wait for a full
+//! retrieval. Subscriptions that have not been requested for a while are forgotten.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use rocket::futures::{stream, StreamExt};
+use rocket::serde::{Deserialize, Serialize};
+use tokio::time;
+
+use crate::archive::Archive;
+use crate::backends::Registry;
+use crate::cache::Cache;
+use crate::Config;
+
+/// The default interval between refreshes (in seconds).
+const DEFAULT_INTERVAL: u64 = 300;
+
+/// The default time after which subscriptions that are not requested are forgotten (in seconds).
+const DEFAULT_FORGET_AFTER: u64 = 7 * 86400;
+
+/// The default maximum number of subscriptions that are remembered.
+const DEFAULT_MAX_SUBSCRIPTIONS: usize = 1000;
+
+/// The default maximum number of subscriptions that are refreshed concurrently.
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// The background refresh configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct RefreshConfig {
+    /// Whether refreshing feeds in the background is enabled.
+    enabled: bool,
+
+    /// The interval between refreshes (in seconds).
+    interval: u64,
+
+    /// The time after which a subscription that is not requested anymore is forgotten (in
+    /// seconds).
+    forget_after: u64,
+
+    /// The maximum number of subscriptions that are remembered.
+    max_subscriptions: usize,
+
+    /// The maximum number of subscriptions that are refreshed concurrently.
+    max_concurrent: usize,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: DEFAULT_INTERVAL,
+            forget_after: DEFAULT_FORGET_AFTER,
+            max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+        }
+    }
+}
+
+/// A subscription to a feed, i.e. a channel on a back-end with an item limit.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct Subscription {
+    /// The ID of the back-end.
+    backend_id: String,
+
+    /// The ID of the channel.
+    channel_id: String,
+
+    /// The item limit (if any).
+    item_limit: Option<usize>,
+}
+
+/// The subscriptions to feeds with the timestamps they were last requested.
+///
+/// The subscriptions can be cheaply cloned; all clones share the same subscriptions.
+#[derive(Clone, Debug)]
+pub(crate) struct Subscriptions {
+    /// The subscriptions with the timestamps they were last requested.
+    entries: Arc<Mutex<HashMap<Subscription, DateTime<Utc>>>>,
+
+    /// The maximum number of subscriptions.
+    max_count: usize,
+}
+
+impl Subscriptions {
+    /// Creates the (empty) subscriptions using the provided configuration.
+    pub(crate) fn new(config: &RefreshConfig) -> Self {
+        Self {
+            entries: Default::default(),
+            max_count: config.max_subscriptions,
+        }
+    }
+
+    /// Records that the feed of a channel on a back-end with an item limit was requested.
+    ///
+    /// If the maximum number of subscriptions is reached, the subscription that was requested
+    /// least recently is forgotten to make room for a new one.
+    pub(crate) fn record(&self, backend_id: &str, channel_id: &str, item_limit: Option<usize>) {
+        let subscription = Subscription {
+            backend_id: backend_id.to_owned(),
+            channel_id: channel_id.to_owned(),
+            item_limit,
+        };
+
+        let mut subscriptions = self.entries.lock().expect("lock is not poisoned");
+        if !subscriptions.contains_key(&subscription) && subscriptions.len() >= self.max_count {
+            let least_recent = subscriptions
+                .iter()
+                .min_by_key(|(_, requested_at)| **requested_at)
+                .map(|(subscription, _)| subscription.clone());
+            match least_recent {
+                Some(least_recent) => {
+                    subscriptions.remove(&least_recent);
+                }
+                None => return,
+            }
+        }
+        subscriptions.insert(subscription, Utc::now());
+    }
+
+    /// Returns the subscriptions that have been requested since the provided timestamp.
+    ///
+    /// Subscriptions that have not been requested since are forgotten.
+    fn requested_since(&self, since: DateTime<Utc>) -> Vec<Subscription> {
+        let mut subscriptions = self.entries.lock().expect("lock is not poisoned");
+        subscriptions.retain(|_, requested_at| *requested_at >= since);
+
+        subscriptions.keys().cloned().collect()
+    }
+}
+
+/// Runs the background refresh task (if enabled).
+///
+/// Each interval, the channels of all subscriptions are retrieved (a limited number at a time)
+/// using a cache handle that refreshes the entries that would expire before the next refresh. The
+/// retrieved items are archived as well (if enabled for the back-end).
+pub(crate) async fn run(
+    registry: Registry,
+    config: Config,
+    cache: Cache,
+    archive: Archive,
+    subscriptions: Subscriptions,
+) {
+    let refresh_config = &config.refresh;
+    if !refresh_config.enabled {
+        return;
+    }
+
+    let interval = Duration::seconds(refresh_config.interval.max(1) as i64);
+    let forget_after = Duration::seconds(refresh_config.forget_after as i64);
+    // Refresh entries that would expire before the refresh after the next one, so that there is
+    // always some leeway for slow retrievals. Entries with a shorter time-to-live are only
+    // refreshed in the second half of their lifetime (see `Cache::refreshing_ahead`).
+    let cache = cache.refreshing_ahead(interval * 2);
+    let max_concurrent = refresh_config.max_concurrent.max(1);
+    let mut timer = time::interval(interval.to_std().expect("interval is not negative"));
+    loop {
+        timer.tick().await;
+
+        let subscriptions = subscriptions.requested_since(Utc::now() - forget_after);
+        stream::iter(subscriptions)
+            .for_each_concurrent(max_concurrent, |subscription| {
+                refresh(&registry, &config, &cache, &archive, subscription)
+            })
+            .await;
+    }
+}
+
+/// Refreshes the channel of the provided subscription and archives its items (if enabled).
+async fn refresh(
+    registry: &Registry,
+    config: &Config,
+    cache: &Cache,
+    archive: &Archive,
+    subscription: Subscription,
+) {
+    let Subscription {
+        backend_id,
+        channel_id,
+        item_limit,
+    } = subscription;
+    let backend = match registry.get(&backend_id, config, cache) {
+        Ok(backend) => backend,
+        Err(err) => {
+            eprintln!("💥 Could not refresh channel {channel_id} on {backend_id}: {err}");
+            return;
+        }
+    };
+
+    match backend.channel(&channel_id, item_limit).await {
+        Ok(channel) => {
+            let client = config.backend(&backend_id).http_client();
+            archive.update(backend, client, &backend_id, &channel_id, &channel.items);
+        }
+        Err(err) => {
+            eprintln!("💥 Could not refresh channel {channel_id} on {backend_id}: {err}");
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Transcoding of media content using a local ffmpeg process.
+//!
+//! Some podcast clients cannot play the media formats that the back-ends provide. For these,
+//! the media content can be transcoded on the fly to a more widely supported format.
+
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use rocket::http::ContentType;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{FromFormField, Request, UriDisplayQuery};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::backends::Enclosure;
+use crate::{Error, Result};
+
+/// The default bitrate of transcoded media content (in kbps).
+const DEFAULT_BITRATE: u32 = 128;
+
+/// The default path of the ffmpeg executable.
+const DEFAULT_FFMPEG_PATH: &str = "ffmpeg";
+
+/// The default maximum number of concurrently running ffmpeg processes.
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// The transcoding configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct TranscodeConfig {
+    /// The path of the ffmpeg executable.
+    ffmpeg_path: PathBuf,
+
+    /// The bitrate of transcoded media content (in kbps).
+    bitrate: u32,
+
+    /// The maximum number of concurrently running ffmpeg processes.
+    max_concurrent: usize,
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> Self {
+        Self {
+            ffmpeg_path: PathBuf::from(DEFAULT_FFMPEG_PATH),
+            bitrate: DEFAULT_BITRATE,
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+        }
+    }
+}
+
+/// The limit on the number of concurrently running ffmpeg processes.
+///
+/// A limit can be cheaply cloned; all clones share the same permits.
+#[derive(Clone, Debug)]
+pub(crate) struct TranscodeLimit(Arc<Semaphore>);
+
+impl TranscodeLimit {
+    /// Creates the limit using the provided configuration.
+    pub(crate) fn new(config: &TranscodeConfig) -> Self {
+        Self(Arc::new(Semaphore::new(config.max_concurrent)))
+    }
+}
+
+/// The media formats that media content can be transcoded to.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, FromFormField, UriDisplayQuery,
+)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub(crate) enum MediaFormat {
+    /// MPEG-1 Audio Layer III.
+    #[field(value = "mp3")]
+    Mp3,
+}
+
+impl MediaFormat {
+    /// Returns the MIME type of the media format.
+    pub(crate) fn mime_type(&self) -> &'static str {
+        match self {
+            MediaFormat::Mp3 => "audio/mpeg",
+        }
+    }
+
+    /// Returns the file extension of the media format.
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            MediaFormat::Mp3 => "mp3",
+        }
+    }
+
+    /// Returns the ffmpeg audio codec and output format of the media format.
+    fn ffmpeg_codec_and_format(&self) -> (&'static str, &'static str) {
+        match self {
+            MediaFormat::Mp3 => ("libmp3lame", "mp3"),
+        }
+    }
+}
+
+/// Returns the enclosure for the media content transcoded to the provided format.
+///
+/// The length of the transcoded media content is estimated using the duration (if known) and
+/// the configured bitrate.
+pub(crate) fn transcoded_enclosure(
+    config: &TranscodeConfig,
+    enclosure: Enclosure,
+    format: MediaFormat,
+    duration: Option<u32>,
+) -> Enclosure {
+    let length = duration
+        .map(|duration| config.bitrate as u64 * 1000 * duration as u64 / 8)
+        .unwrap_or(enclosure.length);
+
+    Enclosure {
+        file: enclosure.file.with_extension(format.extension()),
+        mime_type: String::from(format.mime_type()),
+        length,
+    }
+}
+
+/// Media content that is transcoded by an ffmpeg process.
+///
+/// The ffmpeg process is killed when the transcoded media content is dropped, e.g. when the
+/// client disconnects.
+#[derive(Debug)]
+pub(crate) struct TranscodedMedia {
+    /// The running ffmpeg process.
+    _process: Child,
+
+    /// The permit of the transcode limit that is held while the process runs.
+    _permit: OwnedSemaphorePermit,
+
+    /// The standard output of the ffmpeg process that produces the transcoded media content.
+    output: ChildStdout,
+
+    /// The format of the transcoded media content.
+    format: MediaFormat,
+}
+
+impl AsyncRead for TranscodedMedia {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.output).poll_read(cx, buf)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for TranscodedMedia {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        let content_type = ContentType::parse_flexible(self.format.mime_type())
+            .expect("MIME type can always be parsed");
+
+        Response::build()
+            .header(content_type)
+            .raw_header("Accept-Ranges", "none")
+            .streamed_body(self)
+            .ok()
+    }
+}
+
+/// Starts transcoding the media content at the provided URL to the provided format.
+///
+/// If the maximum number of ffmpeg processes are already running, this fails right away instead
+/// of waiting for one of them to finish.
+pub(crate) fn transcode(
+    config: &TranscodeConfig,
+    limit: &TranscodeLimit,
+    url: &str,
+    format: MediaFormat,
+) -> Result<TranscodedMedia> {
+    let permit = limit
+        .0
+        .clone()
+        .try_acquire_owned()
+        .map_err(|_| Error::TranscodeBusy)?;
+    println!("🎞️ Transcoding media content from {url} to {format:?}...");
+    let (codec, output_format) = format.ffmpeg_codec_and_format();
+    let mut process = Command::new(&config.ffmpeg_path)
+        .args(["-nostdin", "-loglevel", "error", "-i", url, "-vn"])
+        .args(["-codec:a", codec, "-b:a", &format!("{}k", config.bitrate)])
+        .args(["-f", output_format, "pipe:1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let output = process.stdout.take().ok_or(Error::TranscodeFailed)?;
+
+    Ok(TranscodedMedia {
+        _process: process,
+        _permit: permit,
+        output,
+        format,
+    })
+}
@@ -0,0 +1,197 @@
+//! Serving of the transcripts of the media content of items.
+//!
+//! Back-ends provide transcripts in WebVTT format. These are served as-is or converted to SRT,
+//! so that podcast clients can pick the format they support (see the
+//! [`podcast:transcript`](https://podcasting2.org/podcast-namespace/tags/transcript) tag).
+
+use std::path::Path;
+
+use crate::backends::Item;
+
+/// The formats that transcripts are served in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TranscriptFormat {
+    /// Web Video Text Tracks.
+    Vtt,
+
+    /// SubRip.
+    Srt,
+}
+
+impl TranscriptFormat {
+    /// All the formats that transcripts are served in.
+    pub(crate) const ALL: [TranscriptFormat; 2] = [TranscriptFormat::Vtt, TranscriptFormat::Srt];
+
+    /// Returns the format for the extension of the provided transcript file path (if supported).
+    pub(crate) fn from_path(file: &Path) -> Option<Self> {
+        match file.extension()?.to_str()? {
+            "vtt" => Some(TranscriptFormat::Vtt),
+            "srt" => Some(TranscriptFormat::Srt),
+            _ => None,
+        }
+    }
+
+    /// Returns the file extension of the format.
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Vtt => "vtt",
+            TranscriptFormat::Srt => "srt",
+        }
+    }
+
+    /// Returns the MIME type of the format.
+    pub(crate) fn mime_type(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Vtt => "text/vtt",
+            TranscriptFormat::Srt => "application/x-subrip",
+        }
+    }
+
+    /// Converts the provided transcript in WebVTT format to this format.
+    pub(crate) fn convert(&self, vtt: String) -> String {
+        match self {
+            TranscriptFormat::Vtt => vtt,
+            TranscriptFormat::Srt => vtt_to_srt(&vtt),
+        }
+    }
+}
+
+/// Retains only the transcripts of the items in the provided languages.
+///
+/// A language matches if it is equal to or a more specific variant of one of the provided
+/// languages (e.g. `en-US` matches `en`). If no languages are provided, all transcripts are
+/// retained.
+pub(crate) fn retain_languages(items: &mut [Item], languages: &[String]) {
+    if languages.is_empty() {
+        return;
+    }
+
+    for item in items {
+        item.transcripts.retain(|transcript| {
+            languages.iter().any(|language| {
+                let language = language.trim();
+                transcript.language == language
+                    || transcript
+                        .language
+                        .strip_prefix(language)
+                        .is_some_and(|rest| rest.starts_with('-'))
+            })
+        });
+    }
+}
+
+/// Converts a transcript in WebVTT format to SRT format.
+///
+/// Cue identifiers, cue settings, (timing) tags and blocks other than cues are dropped.
+fn vtt_to_srt(vtt: &str) -> String {
+    let vtt = vtt.replace("\r\n", "\n");
+    let mut srt = String::new();
+    let mut index = 0;
+
+    for block in vtt.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let mut times = timing.split_whitespace().filter(|part| *part != "-->");
+        let (Some(start), Some(end)) = (times.next(), times.next()) else {
+            continue;
+        };
+        let text = lines
+            .map(strip_tags)
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        if text.is_empty() {
+            continue;
+        }
+
+        index += 1;
+        srt.push_str(&format!(
+            "{index}\n{} --> {}\n{}\n\n",
+            srt_timestamp(start),
+            srt_timestamp(end),
+            text.join("\n")
+        ));
+    }
+
+    srt
+}
+
+/// Converts a WebVTT timestamp (`[HH:]MM:SS.mmm`) to an SRT timestamp (`HH:MM:SS,mmm`).
+fn srt_timestamp(timestamp: &str) -> String {
+    let timestamp = timestamp.replace('.', ",");
+    if timestamp.matches(':').count() < 2 {
+        format!("00:{timestamp}")
+    } else {
+        timestamp
+    }
+}
+
+/// Strips the (timing, class, voice, etc.) tags from a line of cue text.
+///
+/// The character references that are needed for escaping in WebVTT are unescaped as well.
+fn strip_tags(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    unescape_entities(&stripped)
+}
+
+/// Unescapes the character references that are commonly used for escaping in HTML and WebVTT.
+///
+/// The ampersand is unescaped last, so that escaped references (like `&amp;lt;`) stay intact.
+pub(crate) fn unescape_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vtt_to_srt_conversion() {
+        let vtt = "WEBVTT\r\n\
+                   Kind: captions\r\n\
+                   Language: en\r\n\
+                   \r\n\
+                   NOTE This is a comment\r\n\
+                   \r\n\
+                   intro\r\n\
+                   00:00.500 --> 00:02.000 align:start position:0%\r\n\
+                   <v Host>Welcome &amp; <c.yellow>hello</c></v>\r\n\
+                   \r\n\
+                   00:01:02.250 --> 01:00:03.000\r\n\
+                   First line\r\n\
+                   Second line\r\n\
+                   \r\n\
+                   00:01:04.000 --> 00:01:05.000\r\n\
+                   <00:01:04.500><c> </c>\r\n";
+        assert_eq!(
+            vtt_to_srt(vtt),
+            "1\n00:00:00,500 --> 00:00:02,000\nWelcome & hello\n\n\
+             2\n00:01:02,250 --> 01:00:03,000\nFirst line\nSecond line\n\n"
+        );
+    }
+
+    #[test]
+    fn unescape_entities_once() {
+        assert_eq!(
+            unescape_entities("&quot;A&quot; &lt;b&gt; &#39;c&#39; &amp;lt;"),
+            "\"A\" <b> 'c' &lt;"
+        );
+    }
+}
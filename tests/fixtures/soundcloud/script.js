@@ -0,0 +1 @@
+(window.webpackJsonp=window.webpackJsonp||[]).push([[42],{1337:function(e,t,n){"use strict";n.d(t,"a",function(){return o});var r=n(12),i=n(34);function o(e){return Object(r.a)({},e,{app_version:"1716891234",app_locale:"en"})}},1338:function(e,t,n){var a={client_id:"a1B2c3D4e5F6g7H8i9J0k1L2m3N4o5P6",env:"production",api_host:"api-v2.soundcloud.com"};e.exports=a}}]);
@@ -0,0 +1 @@
+(window.webpackJsonp=window.webpackJsonp||[]).push([[7],{99:function(e,t,n){var a={client_id:"",env:"production"};e.exports=a}}]);